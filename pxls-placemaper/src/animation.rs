@@ -0,0 +1,556 @@
+use anyhow::Result;
+use gif::{DisposalMethod, Encoder, Frame, Repeat};
+use image::{GrayImage, Luma};
+use log::info;
+use std::{
+	collections::VecDeque,
+	fmt::Write as _,
+	fs::{self, File},
+	io::{self, BufWriter, Write},
+	path::{Path, PathBuf},
+};
+
+use crate::{font::*, structure::*};
+
+/// Frames kept before encoding, undo can only retract the last frame
+const LOOKBACK: usize = 1;
+
+/// Average of `ease_weight` over the animation
+const EASE_AVERAGE: f32 = 1.5 - 2.0 / std::f32::consts::PI;
+
+pub struct AnimationFrame {
+	pub img: GrayImageCropped,
+	/// User pixels placed so far
+	pub pixels: u32,
+	/// Log date of the last pixel in frame
+	pub date: String,
+}
+
+/// Hand frames to every output as they are produced
+pub struct AnimationStream {
+	lookback: VecDeque<AnimationFrame>,
+	gif: GifWriter,
+	png_frames: Option<PngSequence>,
+}
+
+impl AnimationStream {
+	/// `gif_path` is also the base name of PNG frames folder,
+	/// `total_frames` is required for easing
+	pub fn new(
+		gif_path: &Path,
+		width: u32,
+		height: u32,
+		pal_vec: &PaletteVec,
+		settings: &Settings,
+		total_frames: Option<u32>,
+	) -> Result<Self> {
+		let gif = GifWriter::new(gif_path, width, height, pal_vec, settings, total_frames)?;
+		let png_frames = settings
+			.png_frames
+			.map(|mode| {
+				let stem = gif_path.file_stem().unwrap_or_default().to_string_lossy();
+				let dir = gif_path.with_file_name(format!("{stem} Frames"));
+				PngSequence::new(dir, mode, width, height, pal_vec)
+			})
+			.transpose()?;
+
+		let mut animation = Self {
+			lookback: VecDeque::with_capacity(LOOKBACK + 1),
+			gif,
+			png_frames,
+		};
+
+		let intial = GrayImage::from_pixel(width, height, Luma([pal_vec.blank_index]));
+		animation.push(AnimationFrame {
+			img: GrayImageCropped::new_pure(intial),
+			pixels: 0,
+			date: String::new(),
+		})?;
+		Ok(animation)
+	}
+
+	pub fn push(&mut self, frame: AnimationFrame) -> Result<()> {
+		self.lookback.push_back(frame);
+		while self.lookback.len() > LOOKBACK {
+			let oldest = self.lookback.pop_front().unwrap();
+			self.write(&oldest, false)?;
+		}
+		Ok(())
+	}
+
+	/// Retract the last frame not yet written
+	pub fn pop(&mut self) -> Option<AnimationFrame> {
+		self.lookback.pop_back()
+	}
+
+	/// GIF files written, numbered parts when split
+	pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+		while let Some(frame) = self.lookback.pop_front() {
+			let is_last = self.lookback.is_empty();
+			self.write(&frame, is_last)?;
+		}
+		let gif_paths = self.gif.finish()?;
+		if let Some(png_frames) = self.png_frames {
+			png_frames.finish()?;
+		}
+		Ok(gif_paths)
+	}
+
+	fn write(&mut self, frame: &AnimationFrame, is_last: bool) -> Result<()> {
+		self.gif.write(frame, is_last)?;
+		if let Some(png_frames) = self.png_frames.as_mut() {
+			png_frames.write(frame)?;
+		}
+		Ok(())
+	}
+}
+
+struct GifWriter {
+	encoder: Encoder<CountWriter<BufWriter<File>>>,
+	path: PathBuf,
+	/// Every file started so far
+	written_paths: Vec<PathBuf>,
+	width: u32,
+	height: u32,
+	repeat: Repeat,
+	palette: Vec<u8>,
+	transparent: u8,
+	frame_delay: u16,
+	hold: Option<u16>,
+	/// Total frames when eased
+	ease_frames: Option<u32>,
+	written: u32,
+	split: Option<SplitPart>,
+	overlay: Option<TextOverlay>,
+}
+
+/// Current part of a split animation
+struct SplitPart {
+	max_frames: Option<u32>,
+	max_bytes: Option<u64>,
+	part: u32,
+	part_frames: u32,
+	/// Full state of everything written, keyframe of the next part
+	canvas: GrayImage,
+}
+
+/// Bytes written to the GIF file so far
+struct CountWriter<W: Write> {
+	inner: W,
+	bytes: u64,
+}
+
+impl<W: Write> Write for CountWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let written = self.inner.write(buf)?;
+		self.bytes += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+impl GifWriter {
+	fn new(
+		path: &Path,
+		width: u32,
+		height: u32,
+		pal_vec: &PaletteVec,
+		Settings {
+			name,
+			frame_delay,
+			gif:
+				GifSetting {
+					repeat,
+					hold,
+					ease,
+					max_frames,
+					max_size,
+					overlay,
+					..
+				},
+			..
+		}: &Settings,
+		total_frames: Option<u32>,
+	) -> Result<Self> {
+		let repeat = match repeat {
+			GifRepeat::Finite(times) => Repeat::Finite(*times),
+			GifRepeat::Infinite => Repeat::Infinite,
+		};
+
+		let split = (max_frames.is_some() || max_size.is_some()).then(|| SplitPart {
+			max_frames: *max_frames,
+			max_bytes: max_size.map(|megabytes| megabytes as u64 * 1024 * 1024),
+			part: 1,
+			part_frames: 0,
+			canvas: GrayImage::from_pixel(width, height, Luma([pal_vec.blank_index])),
+		});
+
+		let palette = pal_vec.flat_palette();
+		let first_path = match split {
+			Some(_) => part_path(path, 1),
+			None => path.to_path_buf(),
+		};
+		let encoder = new_encoder(&first_path, width, height, &palette, repeat)?;
+
+		Ok(Self {
+			encoder,
+			path: path.to_path_buf(),
+			written_paths: vec![first_path],
+			width,
+			height,
+			repeat,
+			palette,
+			transparent: pal_vec.blank_index,
+			frame_delay: *frame_delay,
+			hold: *hold,
+			ease_frames: total_frames.filter(|_| *ease),
+			written: 0,
+			split,
+			overlay: overlay
+				.as_ref()
+				.map(|setting| TextOverlay::new(setting, name, width, height, pal_vec)),
+		})
+	}
+
+	fn finish(self) -> Result<Vec<PathBuf>> {
+		self.encoder.into_inner()?.flush()?;
+		info!("Encoded animated placemap.");
+		Ok(self.written_paths)
+	}
+
+	/// Longer at both ends, shorter in the middle, same total length
+	fn delay(&self) -> u16 {
+		let Some(total_frames) = self.ease_frames else {
+			return self.frame_delay;
+		};
+		let progress = self.written as f32 / total_frames.saturating_sub(1).max(1) as f32;
+		let ease_weight = 1.5 - (progress.min(1.0) * std::f32::consts::PI).sin();
+		let delay = self.frame_delay as f32 * ease_weight / EASE_AVERAGE;
+		(delay.round() as u16).max(1)
+	}
+
+	/// Start the next part with a keyframe when the next frame doesn't fit in current part
+	fn split_if_full(&mut self, next_bytes: u64) -> Result<()> {
+		let Some(split) = self.split.as_mut() else {
+			return Ok(());
+		};
+		// GIF trailer is 1 byte
+		let bytes = self.encoder.get_ref().bytes + next_bytes + 1;
+		let full = split
+			.max_frames
+			.is_some_and(|max_frames| split.part_frames >= max_frames)
+			|| split
+				.max_bytes
+				.is_some_and(|max_bytes| split.part_frames > 0 && bytes > max_bytes);
+		if !full {
+			return Ok(());
+		}
+
+		split.part += 1;
+		split.part_frames = 0;
+		let keyframe = GrayImageCropped::new_pure(split.canvas.clone());
+		let next_path = part_path(&self.path, split.part);
+		info!("Encoding animated placemap part {}.", split.part);
+
+		let next_encoder = new_encoder(
+			&next_path,
+			self.width,
+			self.height,
+			&self.palette,
+			self.repeat,
+		)?;
+		std::mem::replace(&mut self.encoder, next_encoder)
+			.into_inner()?
+			.flush()?;
+		self.written_paths.push(next_path);
+
+		let keyframe = self.encode(&keyframe, self.frame_delay);
+		self.put(&keyframe)
+	}
+
+	fn write(&mut self, frame: &AnimationFrame, is_last: bool) -> Result<()> {
+		let with_text = self
+			.overlay
+			.as_ref()
+			.map(|overlay| overlay.apply(frame, self.transparent));
+		let img = with_text.as_ref().unwrap_or(&frame.img);
+
+		let delay = match self.hold {
+			Some(hold) if is_last => hold,
+			_ => self.delay(),
+		};
+		let encoded = self.encode(img, delay);
+		self.split_if_full(self.encoded_size(&encoded))?;
+
+		// After the split, keyframe is the state before this frame
+		if let Some(split) = self.split.as_mut() {
+			split.canvas.paint(img, self.transparent);
+		}
+		self.put(&encoded)?;
+		self.written += 1;
+		Ok(())
+	}
+
+	/// Compressed frame, size known before writing
+	fn encode(
+		&self,
+		GrayImageCropped { left, top, img }: &GrayImageCropped,
+		delay: u16,
+	) -> Frame<'static> {
+		let mut frame = Frame {
+			delay,
+			dispose: DisposalMethod::Any,
+			transparent: Some(self.transparent),
+			left: *left,
+			top: *top,
+			width: img.width() as u16,
+			height: img.height() as u16,
+			palette: Some(self.palette.clone()),
+			buffer: img.as_raw().clone().into(),
+			..Default::default()
+		};
+		frame.make_lzw_pre_encoded();
+		frame
+	}
+
+	/// Bytes `put` will write: control extension, descriptor, local palette, data blocks
+	fn encoded_size(&self, frame: &Frame) -> u64 {
+		let palette_entries = (self.palette.len() / 3).next_power_of_two().max(2);
+		let data = frame.buffer.len().saturating_sub(1);
+		(8 + 10 + palette_entries * 3 + 1 + data + data.div_ceil(255) + 1) as u64
+	}
+
+	fn put(&mut self, frame: &Frame) -> Result<()> {
+		self.encoder.write_lzw_pre_encoded_frame(frame)?;
+		if let Some(split) = self.split.as_mut() {
+			split.part_frames += 1;
+		}
+		Ok(())
+	}
+}
+
+fn new_encoder(
+	path: &Path,
+	width: u32,
+	height: u32,
+	palette: &[u8],
+	repeat: Repeat,
+) -> Result<Encoder<CountWriter<BufWriter<File>>>> {
+	let gif_file = CountWriter {
+		inner: BufWriter::new(File::create(path)?),
+		bytes: 0,
+	};
+	let mut encoder = Encoder::new(gif_file, width as u16, height as u16, palette)?;
+	encoder.set_repeat(repeat)?;
+	Ok(encoder)
+}
+
+/// "Name.gif" to "Name {part}.gif"
+fn part_path(path: &Path, part: u32) -> PathBuf {
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+	path.with_file_name(format!("{stem} {part}.gif"))
+}
+
+/// Name, pixel counter and date in a corner box
+struct TextOverlay {
+	name: Option<String>,
+	counter: bool,
+	date: bool,
+	corner: Corner,
+	scale: u32,
+	width: u32,
+	height: u32,
+	ink: u8,
+	background: u8,
+}
+
+impl TextOverlay {
+	fn new(
+		OverlaySetting {
+			corner,
+			scale,
+			name: show_name,
+			counter,
+			date,
+		}: &OverlaySetting,
+		name: &str,
+		width: u32,
+		height: u32,
+		pal_vec: &PaletteVec,
+	) -> Self {
+		let luminance = |index: &usize| {
+			let [r, g, b, _a] = pal_vec.info[*index].rgba.0;
+			r as u32 * 299 + g as u32 * 587 + b as u32 * 114
+		};
+		let indexes = 0..pal_vec.info.len();
+		Self {
+			name: show_name.then(|| name.to_owned()),
+			counter: *counter,
+			date: *date,
+			corner: *corner,
+			scale: (*scale).max(1),
+			width,
+			height,
+			ink: indexes.clone().max_by_key(luminance).unwrap_or_default() as u8,
+			background: indexes.min_by_key(luminance).unwrap_or_default() as u8,
+		}
+	}
+
+	fn lines(&self, AnimationFrame { pixels, date, .. }: &AnimationFrame) -> Vec<String> {
+		let mut lines = Vec::with_capacity(3);
+		if let Some(name) = &self.name {
+			lines.push(name.clone());
+		}
+		if self.counter {
+			lines.push(format!("{pixels} PX"));
+		}
+		if self.date {
+			// "YYYY-MM-DD HH:MM"
+			lines.push(date.get(..16).unwrap_or(date).to_owned());
+		}
+		lines
+	}
+
+	/// Frame grown to cover the text box
+	fn apply(&self, frame: &AnimationFrame, transparent: u8) -> GrayImageCropped {
+		let lines = self.lines(frame);
+		let GrayImageCropped { left, top, img } = &frame.img;
+		if lines.is_empty() {
+			return GrayImageCropped::new(*left, *top, img.clone());
+		}
+
+		let pad = self.scale;
+		let line_height = GLYPH_HEIGHT * self.scale + pad;
+		let text_width = lines
+			.iter()
+			.map(|line| text_size(line, self.scale).0)
+			.max()
+			.unwrap_or_default();
+		let box_width = (text_width + pad * 2).min(self.width);
+		let box_height = (lines.len() as u32 * line_height + pad).min(self.height);
+		let (box_left, box_top) = match self.corner {
+			Corner::TopLeft => (0, 0),
+			Corner::TopRight => (self.width - box_width, 0),
+			Corner::BottomLeft => (0, self.height - box_height),
+			Corner::BottomRight => (self.width - box_width, self.height - box_height),
+		};
+
+		let (left, top) = (*left as u32, *top as u32);
+		let union_left = left.min(box_left);
+		let union_top = top.min(box_top);
+		let union_right = (left + img.width()).max(box_left + box_width);
+		let union_bottom = (top + img.height()).max(box_top + box_height);
+
+		let mut union = GrayImage::from_pixel(
+			union_right - union_left,
+			union_bottom - union_top,
+			Luma([transparent]),
+		);
+		union.paint(
+			&GrayImageCropped::new(
+				(left - union_left) as u16,
+				(top - union_top) as u16,
+				img.clone(),
+			),
+			transparent,
+		);
+
+		let (inner_left, inner_top) = (box_left - union_left, box_top - union_top);
+		for y in inner_top..inner_top + box_height {
+			for x in inner_left..inner_left + box_width {
+				union.put_pixel(x, y, Luma([self.background]));
+			}
+		}
+		for (n, line) in lines.iter().enumerate() {
+			draw_text(
+				&mut union,
+				inner_left + pad,
+				inner_top + pad + n as u32 * line_height,
+				line,
+				self.scale,
+				self.ink,
+			);
+		}
+
+		GrayImageCropped::new(union_left as u16, union_top as u16, union)
+	}
+}
+
+/// Indexed PNG per frame with a manifest
+struct PngSequence {
+	dir: PathBuf,
+	mode: PngFrames,
+	/// Full state of everything written
+	canvas: GrayImage,
+	palette: Vec<u8>,
+	/// Alpha per palette index, blank is transparent
+	trns: Vec<u8>,
+	transparent: u8,
+	manifest: String,
+	index: u32,
+}
+
+impl PngSequence {
+	fn new(
+		dir: PathBuf,
+		mode: PngFrames,
+		width: u32,
+		height: u32,
+		pal_vec: &PaletteVec,
+	) -> Result<Self> {
+		fs::create_dir_all(&dir)?;
+		let blank = pal_vec.blank_index;
+		let mut trns = vec![255; blank as usize + 1];
+		trns[blank as usize] = 0;
+
+		Ok(Self {
+			dir,
+			mode,
+			canvas: GrayImage::from_pixel(width, height, Luma([blank])),
+			palette: pal_vec.flat_palette(),
+			trns,
+			transparent: blank,
+			manifest: String::from("Frame\tFile\tPixels\tDate\tLeft\tTop\tWidth\tHeight\n"),
+			index: 0,
+		})
+	}
+
+	fn write(&mut self, AnimationFrame { img, pixels, date }: &AnimationFrame) -> Result<()> {
+		self.canvas.paint(img, self.transparent);
+
+		let (left, top, frame_img) = match self.mode {
+			PngFrames::Full => (0, 0, &self.canvas),
+			PngFrames::Cropped => (img.left, img.top, &img.img),
+		};
+
+		let file_name = format!("{:06}.png", self.index);
+		let png_file = BufWriter::new(File::create(self.dir.join(&file_name))?);
+		let mut encoder = png::Encoder::new(png_file, frame_img.width(), frame_img.height());
+		encoder.set_color(png::ColorType::Indexed);
+		encoder.set_depth(png::BitDepth::Eight);
+		encoder.set_palette(self.palette.as_slice());
+		encoder.set_trns(self.trns.as_slice());
+		encoder
+			.write_header()?
+			.write_image_data(frame_img.as_raw())?;
+
+		writeln!(
+			&mut self.manifest,
+			"{}\t{file_name}\t{pixels}\t{date}\t{left}\t{top}\t{}\t{}",
+			self.index,
+			frame_img.width(),
+			frame_img.height()
+		)?;
+		self.index += 1;
+		Ok(())
+	}
+
+	fn finish(self) -> Result<()> {
+		fs::write(self.dir.join("manifest.tsv"), self.manifest)?;
+		info!("Saved {} PNG frames.", self.index);
+		Ok(())
+	}
+}
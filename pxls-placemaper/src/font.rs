@@ -0,0 +1,87 @@
+use image::{GrayImage, Luma};
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// 5x7 glyph, one byte per row, highest of 5 bits is the left column
+pub fn glyph(ch: char) -> [u8; 7] {
+	match ch.to_ascii_uppercase() {
+		'0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+		'1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+		'2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+		'3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+		'4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+		'5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+		'6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+		'7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+		'8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+		'9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+		'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+		'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+		'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+		'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+		'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+		'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+		'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+		'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+		'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+		'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+		'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+		'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+		'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+		'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+		'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+		'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+		'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+		'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+		'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+		'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+		'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+		'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+		'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+		'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+		'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+		'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+		' ' => [0x00; 7],
+		':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+		'-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+		'.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+		',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+		'/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+		'_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+		'#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+		'(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+		')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+		_ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+	}
+}
+
+/// Width and height of one line, 1 column gap between glyphs
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+	let chars = text.chars().count() as u32;
+	let width = (chars * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale;
+	(width, GLYPH_HEIGHT * scale)
+}
+
+/// Draw one line at x, y, outside of image is skipped
+pub fn draw_text(img: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32, ink: u8) {
+	for (n, ch) in text.chars().enumerate() {
+		let glyph_left = x + n as u32 * (GLYPH_WIDTH + 1) * scale;
+		for (row, bits) in glyph(ch).into_iter().enumerate() {
+			for col in 0..GLYPH_WIDTH {
+				if bits & (0x10 >> col) == 0 {
+					continue;
+				}
+				for dy in 0..scale {
+					for dx in 0..scale {
+						let px_x = glyph_left + col * scale + dx;
+						let px_y = y + row as u32 * scale + dy;
+						if let Some(px) = img.get_pixel_mut_checked(px_x, px_y) {
+							*px = Luma([ink]);
+						}
+					}
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,252 @@
+use std::fmt::Write as _;
+
+use crate::{report::*, structure::*};
+
+const STYLE: &str = "body{font-family:sans-serif;background:#1e1e1e;color:#ddd;margin:2em}
+h1,h2{color:#fff}
+table{border-collapse:collapse;margin:1em 0}
+th,td{border:1px solid #444;padding:2px 8px;text-align:left}
+th{background:#333;cursor:pointer}
+.swatch{display:inline-block;width:1em;height:1em;border:1px solid #888;vertical-align:middle}
+.maps{display:flex;flex-wrap:wrap;gap:1em}
+figure{margin:0}
+figure img{image-rendering:pixelated;max-width:480px;background:#444}
+.bar{fill:#5ec962}";
+
+/// Click a header to sort the table by that column.
+/// Whole numeric cells sort as numbers, dates and names as text
+const SORT_SCRIPT: &str = "document.querySelectorAll('table.sort th').forEach((th,col)=>th.onclick=()=>{
+const body=th.closest('table').tBodies[0],rows=[...body.rows],asc=th.dataset.asc!=='1';
+const key=r=>{const t=r.cells[col].dataset.sort??r.cells[col].innerText,n=Number(t);return t.trim()!==''&&!isNaN(n)?n:t};
+rows.sort((a,b)=>{const x=key(a),y=key(b);return (x>y?1:x<y?-1:0)*(asc?1:-1)});
+th.dataset.asc=asc?'1':'0';rows.forEach(r=>body.appendChild(r))})";
+
+const CHART_WIDTH: u32 = 720;
+const CHART_HEIGHT: u32 = 160;
+
+/// Stats page, images are sibling files in the output folder
+pub fn stats_html(report: &StatsReport, pal_vec: &PaletteVec, images: &[String]) -> String {
+	let mut html = String::with_capacity(16 * 1024);
+
+	let title = match &report.region {
+		Some(region) => format!("C{} {} {}", report.canvas, report.user, region),
+		None => format!("C{} {}", report.canvas, report.user),
+	};
+	let _ = write!(
+		&mut html,
+		"<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>{STYLE}</style></head><body>\n<h1>{}</h1>\n",
+		escape(&title),
+		escape(&title)
+	);
+
+	html.push_str("<table>\n");
+	let mut row = |label: &str, value: String| {
+		let _ = writeln!(
+			&mut html,
+			"<tr><th>{label}</th><td>{}</td></tr>",
+			escape(&value)
+		);
+	};
+	if report.start.is_some() || report.end.is_some() {
+		row(
+			"Window",
+			format!(
+				"{} - {}",
+				report.start.as_deref().unwrap_or("Start"),
+				report.end.as_deref().unwrap_or("End")
+			),
+		);
+	}
+	row("Pixels", report.pixels.to_string());
+	row("Survivor", report.survived.to_string());
+	row("Undo", report.undo.to_string());
+	row("Replace", report.replaced.to_string());
+	row(
+		"Different Position Place",
+		report.diff_pos_place.to_string(),
+	);
+	row("Different Position Undo", report.diff_pos_undo.to_string());
+	for (label, most) in [
+		("Most Placed", &report.most_placed),
+		("Most Undone", &report.most_undone),
+	] {
+		if let Some(PositionReport { x, y, times }) = most {
+			row(label, format!("{x}, {y} ({times} times)"));
+		}
+	}
+	for (label, pixel) in [("First", &report.first), ("Last", &report.last)] {
+		if let Some(PixelReport {
+			date, x, y, color, ..
+		}) = pixel
+		{
+			row(label, format!("{date} at {x}, {y} in {color}"));
+		}
+	}
+	row("Active Days", report.active_days.to_string());
+	if let Some(StreakReport { days, from, to }) = &report.longest_streak {
+		row("Longest Streak", format!("{days} days ({from} - {to})"));
+	}
+	if let Some(HourReport { hour, pixels }) = &report.busiest_hour {
+		row("Busiest Hour", format!("{hour}:00 ({pixels} pixels)"));
+	}
+	html.push_str("</table>\n");
+
+	if !images.is_empty() {
+		html.push_str("<h2>Placemaps</h2>\n<div class=\"maps\">\n");
+		for image in images {
+			let _ = writeln!(
+				&mut html,
+				"<figure><a href=\"{0}\"><img src=\"{0}\" alt=\"{1}\"></a><figcaption>{1}</figcaption></figure>",
+				escape_url(image),
+				escape(image)
+			);
+		}
+		html.push_str("</div>\n");
+	}
+
+	html.push_str("<h2>Pixels Per Day</h2>\n");
+	let days = report
+		.days
+		.iter()
+		.map(|DayReport { day, pixels }| (day.as_str(), *pixels))
+		.collect::<Vec<_>>();
+	bar_chart(&mut html, &days);
+
+	html.push_str("<h2>Place On</h2>\n");
+	let kinds = report
+		.place_kind
+		.iter()
+		.map(|KindReport { kind, pixels }| (*kind, *pixels))
+		.collect::<Vec<_>>();
+	bar_chart(&mut html, &kinds);
+
+	html.push_str("<h2>Top Color</h2>\n");
+	color_table(&mut html, &report.colors, pal_vec);
+
+	if !report.members.is_empty() {
+		html.push_str("<h2>Members</h2>\n<table class=\"sort\"><thead><tr><th>Member</th><th>Pixels</th><th>Survivor</th><th>Undo</th><th>Percent</th></tr></thead><tbody>\n");
+		for MemberReport {
+			name,
+			color,
+			pixels,
+			undo,
+			survived,
+			percent,
+		} in report.members.iter()
+		{
+			let _ = writeln!(
+				&mut html,
+				"<tr><td><span class=\"swatch\" style=\"background:{color}\"></span> {}</td><td>{pixels}</td><td>{survived}</td><td>{undo}</td><td>{percent:.4}</td></tr>",
+				escape(name)
+			);
+		}
+		html.push_str("</tbody></table>\n");
+	}
+
+	if !report.milestones.is_empty() {
+		html.push_str("<h2>Milestones</h2>\n<table class=\"sort\"><thead><tr><th>Place</th><th>Date</th><th>X</th><th>Y</th><th>Color</th><th>Milestone</th></tr></thead><tbody>\n");
+		for MilestoneReport { pixel, kinds } in report.milestones.iter() {
+			let PixelReport {
+				at,
+				date,
+				x,
+				y,
+				color,
+			} = pixel;
+			let _ = writeln!(
+				&mut html,
+				"<tr><td>{at}</td><td>{}</td><td>{x}</td><td>{y}</td><td>{}{}</td><td>{}</td></tr>",
+				escape(date),
+				swatch(pal_vec, color),
+				escape(color),
+				kinds.join(", ")
+			);
+		}
+		html.push_str("</tbody></table>\n");
+	}
+
+	let _ = write!(
+		&mut html,
+		"<script>{SORT_SCRIPT}</script>\n</body></html>\n"
+	);
+	html
+}
+
+fn color_table(html: &mut String, colors: &[ColorReport], pal_vec: &PaletteVec) {
+	html.push_str("<table class=\"sort\"><thead><tr><th>Place</th><th>Color</th><th>Used</th><th>Percent</th></tr></thead><tbody>\n");
+	for ColorReport {
+		rank,
+		name,
+		used,
+		percent,
+	} in colors.iter()
+	{
+		let _ = writeln!(
+			html,
+			"<tr><td>{rank}</td><td>{}{}</td><td>{used}</td><td>{percent:.4}</td></tr>",
+			swatch(pal_vec, name),
+			escape(name)
+		);
+	}
+	html.push_str("</tbody></table>\n");
+}
+
+/// Inline SVG, one bar per value with a tooltip
+fn bar_chart(html: &mut String, values: &[(&str, u32)]) {
+	if values.is_empty() {
+		html.push_str("<p>None</p>\n");
+		return;
+	}
+	let max = values
+		.iter()
+		.map(|(_, value)| *value)
+		.max()
+		.unwrap_or(0)
+		.max(1);
+	let bar_width = (CHART_WIDTH / values.len() as u32).clamp(1, 48);
+	let _ = writeln!(
+		html,
+		"<svg width=\"{}\" height=\"{CHART_HEIGHT}\" viewBox=\"0 0 {} {CHART_HEIGHT}\">",
+		bar_width * values.len() as u32,
+		bar_width * values.len() as u32
+	);
+	for (n, (label, value)) in values.iter().enumerate() {
+		let height = (*value as u64 * CHART_HEIGHT as u64 / max as u64) as u32;
+		let _ = writeln!(
+			html,
+			"<rect class=\"bar\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{height}\"><title>{}: {value}</title></rect>",
+			n as u32 * bar_width,
+			CHART_HEIGHT - height,
+			bar_width.saturating_sub(1).max(1),
+			escape(label)
+		);
+	}
+	html.push_str("</svg>\n");
+}
+
+fn swatch(pal_vec: &PaletteVec, name: &str) -> String {
+	match pal_vec.info.iter().find(|info| info.name == name) {
+		Some(PaletteInfo { rgba, .. }) => {
+			let [r, g, b, _a] = rgba.0;
+			format!("<span class=\"swatch\" style=\"background:#{r:02X}{g:02X}{b:02X}\"></span> ")
+		},
+		None => String::new(),
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+/// Relative file name as link
+fn escape_url(name: &str) -> String {
+	escape(
+		&name
+			.replace('%', "%25")
+			.replace(' ', "%20")
+			.replace('#', "%23"),
+	)
+}
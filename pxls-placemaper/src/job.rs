@@ -0,0 +1,189 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+
+use crate::structure::*;
+
+/// settings.ron with several canvases and users
+/// ```ron
+/// Jobs(
+///     defaults: (pix_per_frame: 25, svg: true),
+///     jobs: [
+///         (name: "c92", canvas_code: "92", palette_code: 13, users: [(name: "Chssam", user_key: "file:chssam.txt")]),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Jobs {
+	/// Options of every job, unless the job sets its own
+	#[serde(default)]
+	pub defaults: JobOptions,
+	pub jobs: Vec<Job>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Job {
+	/// Run only this job with `job <name>`
+	pub name: String,
+	pub canvas_code: String,
+	pub palette_code: u8,
+	/// Each user gets own placemaps and stats
+	#[serde(default)]
+	pub users: Vec<TeamMember>,
+	#[serde(default)]
+	pub options: JobOptions,
+}
+
+/// Same as the `Settings` fields, missing ones come from the defaults.
+/// `to_settings` lists every field of both, a field left out fails to compile
+#[derive(Debug, Default, Deserialize)]
+pub struct JobOptions {
+	#[serde(default)]
+	pub pix_th: Option<Vec<u32>>,
+	#[serde(default)]
+	pub pix_per_frame: Option<u32>,
+	#[serde(default)]
+	pub frame_delay: Option<u16>,
+	#[serde(default)]
+	pub regions: Option<Vec<RegionSetting>>,
+	#[serde(default)]
+	pub gif: Option<GifSetting>,
+	#[serde(default)]
+	pub png_frames: Option<PngFrames>,
+	#[serde(default)]
+	pub svg: Option<bool>,
+	#[serde(default)]
+	pub templates: Option<Vec<String>>,
+	#[serde(default)]
+	pub milestones: Option<MilestoneSetting>,
+	#[serde(default)]
+	pub age_by: Option<AgeBy>,
+	#[serde(default)]
+	pub start: Option<String>,
+	#[serde(default)]
+	pub end: Option<String>,
+	#[serde(default)]
+	pub team: Option<TeamSetting>,
+	#[serde(default)]
+	pub overlap: Option<OverlapSetting>,
+	#[serde(default)]
+	pub output: Option<OutputSetting>,
+}
+
+impl Jobs {
+	/// Settings of the named job or every job.
+	/// `with_keys`: one per user, otherwise one per job for whole canvas commands
+	pub fn to_settings(
+		&self,
+		only: Option<&str>,
+		with_keys: bool,
+	) -> Result<Vec<(String, Settings)>> {
+		for (n, job) in self.jobs.iter().enumerate() {
+			if self.jobs[..n].iter().any(|other| other.name == job.name) {
+				return Err(anyhow!("`jobs[{n}].name`: {:?} is used twice", job.name));
+			}
+		}
+
+		let jobs = match only {
+			Some(name) => {
+				let job = self
+					.jobs
+					.iter()
+					.find(|job| job.name == name)
+					.ok_or_else(|| {
+						let names = self
+							.jobs
+							.iter()
+							.map(|job| job.name.as_str())
+							.collect::<Vec<_>>();
+						anyhow!("No job named {:?}, jobs: {}", name, names.join(", "))
+					})?;
+				vec![job]
+			},
+			None => self.jobs.iter().collect(),
+		};
+
+		let mut settings = Vec::with_capacity(jobs.len());
+		for job in jobs {
+			let options = &job.options;
+			let defaults = &self.defaults;
+			// Every option is used below
+			let JobOptions {
+				pix_th: _,
+				pix_per_frame: _,
+				frame_delay: _,
+				regions: _,
+				gif: _,
+				png_frames: _,
+				svg: _,
+				templates: _,
+				milestones: _,
+				age_by: _,
+				start: _,
+				end: _,
+				team: _,
+				overlap: _,
+				output: _,
+			} = options;
+			let users = match (with_keys, job.users.as_slice()) {
+				// Team replaces the users
+				(true, _) if options.team.is_some() || defaults.team.is_some() => {
+					vec![TeamMember::default()]
+				},
+				(true, []) => {
+					return Err(anyhow!(
+						"Job {}: `users`: needs at least one user",
+						job.name
+					));
+				},
+				(true, users) => users.to_vec(),
+				(false, users) => vec![users.first().cloned().unwrap_or_default()],
+			};
+
+			for TeamMember { name, user_key } in users {
+				settings.push((
+					job.name.clone(),
+					Settings {
+						user_key,
+						name,
+						canvas_code: job.canvas_code.clone(),
+						palette_code: job.palette_code,
+						pix_th: pick(&options.pix_th, &defaults.pix_th)
+							.unwrap_or_else(default_pix_th),
+						pix_per_frame: pick(&options.pix_per_frame, &defaults.pix_per_frame)
+							.unwrap_or_else(default_pix_per_frame),
+						frame_delay: pick(&options.frame_delay, &defaults.frame_delay)
+							.unwrap_or_else(default_frame_delay),
+						regions: pick(&options.regions, &defaults.regions).unwrap_or_default(),
+						gif: pick(&options.gif, &defaults.gif).unwrap_or_default(),
+						png_frames: pick(&options.png_frames, &defaults.png_frames),
+						svg: pick(&options.svg, &defaults.svg).unwrap_or_default(),
+						templates: pick(&options.templates, &defaults.templates)
+							.unwrap_or_default(),
+						milestones: pick(&options.milestones, &defaults.milestones)
+							.unwrap_or_default(),
+						age_by: pick(&options.age_by, &defaults.age_by).unwrap_or_default(),
+						start: pick(&options.start, &defaults.start),
+						end: pick(&options.end, &defaults.end),
+						team: pick(&options.team, &defaults.team),
+						overlap: pick(&options.overlap, &defaults.overlap),
+						output: pick(&options.output, &defaults.output).unwrap_or_default(),
+					},
+				));
+			}
+		}
+		Ok(settings)
+	}
+}
+
+/// Job value first, whole value replaced, not merged
+fn pick<T: Clone>(job: &Option<T>, defaults: &Option<T>) -> Option<T> {
+	job.as_ref().or(defaults.as_ref()).cloned()
+}
+
+/// Jobs file starts with `Jobs(`, after comments and `#![enable(...)]`
+pub fn is_job_list(text: &str) -> bool {
+	text.lines()
+		.map(str::trim)
+		.find(|line| !line.is_empty() && !line.starts_with("//") && !line.starts_with('#'))
+		.is_some_and(|line| line.starts_with("Jobs"))
+}
@@ -1,12 +1,10 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use env_logger::Env;
-use gif::{DisposalMethod, Frame, Repeat};
-use image::{GenericImageView as _, GrayImage, Luma, imageops::overlay};
+use image::{GenericImageView as _, GrayImage, ImageBuffer, Luma, Pixel, imageops::overlay};
 use log::{error, info};
-use sha256::digest;
 use std::{
-	collections::HashMap,
-	fmt::Write as _,
+	borrow::Cow,
+	collections::{HashMap, HashSet},
 	fs::{self, File},
 	io::prelude::*,
 	path::{Path, PathBuf},
@@ -14,8 +12,27 @@ use std::{
 };
 use xz2::read::XzDecoder;
 
+mod animation;
+mod font;
+mod html;
+mod job;
+mod overlap;
+mod render;
+mod report;
+mod snapshot;
 mod structure;
+mod template;
+use animation::*;
+use html::*;
+use job::*;
+use overlap::*;
+use render::*;
+use report::*;
+use snapshot::*;
 use structure::*;
+use template::*;
+
+const EXAMPLE_SETTINGS: &str = include_str!("../settings.example.ron");
 
 fn main() {
 	let begin_time = Instant::now();
@@ -26,7 +43,25 @@ fn main() {
 
 	env_logger::init_from_env(env);
 
-	if let Err(err) = stable_check_run() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	// `job <name> [command]`: one job of a job list
+	let (job, args) = match &args[..] {
+		[select, name, rest @ ..] if select == "job" => (Some(name.as_str()), rest),
+		args => (None, args),
+	};
+	let result = match args.first().map(String::as_str) {
+		None => stable_check_run(job),
+		Some("snapshot") => snapshot_run(&args[1..], job),
+		Some("diff") => diff_run(&args[1..], job),
+		Some("canvas") => canvas_run(job),
+		Some("example") => {
+			print!("{EXAMPLE_SETTINGS}");
+			return;
+		},
+		Some(command) => Err(anyhow!("Unknown command: {command}")),
+	};
+
+	if let Err(err) = result {
 		error!("Unable to process: {:?}", err);
 	};
 
@@ -40,101 +75,256 @@ fn main() {
 	let _ = std::io::stdin().read_line(&mut buf);
 }
 
-fn stable_check_run() -> Result<()> {
+fn stable_check_run(job: Option<&str>) -> Result<()> {
 	let input_dir = Path::new("input");
-	let output_dir = Path::new("output");
 
-	let settings = read_setting()?;
+	for settings in read_setting(input_dir, true, job)? {
+		placemap_run(input_dir, settings)?;
+	}
+
+	Ok(())
+}
+
+/// Placemaps and stats of one user or team
+fn placemap_run(input_dir: &Path, mut settings: Settings) -> Result<()> {
+	if let Some(team) = &settings.team {
+		settings.name = team.name.clone();
+	}
+
+	let output = OutputNaming::new(Path::new("output"), &settings)?;
 
 	let pal_vec = PaletteVec::new(input_dir, settings.palette_code)?;
 
 	let mut image_collection = intial_img(input_dir, &settings.canvas_code, pal_vec.blank_index)?;
 
-	let mut output_info = OutputInfo::new(pal_vec.to_color_used());
+	let regions = settings
+		.regions
+		.iter()
+		.map(|region_setting| {
+			Region::new(
+				input_dir,
+				region_setting,
+				image_collection.place.dimensions(),
+			)
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	let templates = settings
+		.templates
+		.iter()
+		.map(|file_name| ReportTemplate::load(input_dir, file_name))
+		.collect::<Result<Vec<_>>>()?;
+
+	let logs = extract_log(input_dir, &settings.canvas_code)?;
+
+	let mut output_info = OutputInfo::new(pal_vec.to_color_used(), settings.members().len());
+
+	let total_frames = if settings.gif.ease || settings.gif.duration.is_some() {
+		let total_pixels = count_user_pixels(&logs, &settings);
+		if let Some(duration) = settings.gif.duration {
+			let frames = (duration / settings.frame_delay.max(1) as u32).max(1);
+			settings.pix_per_frame = total_pixels.div_ceil(frames).max(1);
+			info!("Pixels per frame: {}", settings.pix_per_frame);
+		}
+		// Intial frame included
+		Some(total_pixels.div_ceil(settings.pix_per_frame) + 1)
+	} else {
+		None
+	};
+
+	let gif_path = output.path(Some(&settings.name), "Placemap Gif.gif")?;
+	let mut animation = AnimationStream::new(
+		&gif_path,
+		image_collection.place.width(),
+		image_collection.place.height(),
+		&pal_vec,
+		&settings,
+		total_frames,
+	)?;
+
+	info!("Encoding animated placemap.");
 
 	process_place_map(
-		input_dir,
+		&logs,
 		&pal_vec,
 		&settings,
+		None,
 		&mut image_collection,
 		&mut output_info,
+		Some(&mut animation),
 	)?;
 
-	save_img_collection(&mut image_collection, output_dir, &settings, &pal_vec)?;
+	let gif_paths = animation.finish()?;
 
-	create_user_stats(output_info, settings, output_dir, pal_vec)?;
+	let mut images = save_img_collection(&image_collection, &output, &settings, None, &pal_vec)?;
+	images.splice(0..0, gif_paths);
+
+	create_user_stats(
+		output_info,
+		&settings,
+		None,
+		&output,
+		&pal_vec,
+		&images,
+		&templates,
+	)?;
+
+	if let Some(overlap_setting) = &settings.overlap {
+		save_overlap_report(
+			&logs,
+			&output,
+			&settings,
+			overlap_setting,
+			&pal_vec,
+			image_collection.place.dimensions(),
+		)?;
+	}
+
+	for region in regions.iter() {
+		info!("Processing region {}...", region.name);
+
+		let mut image_collection =
+			intial_img(input_dir, &settings.canvas_code, pal_vec.blank_index)?;
+
+		let mut output_info = OutputInfo::new(pal_vec.to_color_used(), settings.members().len());
+
+		process_place_map(
+			&logs,
+			&pal_vec,
+			&settings,
+			Some(region),
+			&mut image_collection,
+			&mut output_info,
+			None,
+		)?;
+
+		let images = save_img_collection(
+			&image_collection,
+			&output,
+			&settings,
+			Some(region),
+			&pal_vec,
+		)?;
+
+		create_user_stats(
+			output_info,
+			&settings,
+			Some(region),
+			&output,
+			&pal_vec,
+			&images,
+			&templates,
+		)?;
+	}
+
+	Ok(())
+}
+
+/// `snapshot <timestamp | line>`: whole canvas at that point of the logs
+fn snapshot_run(args: &[String], job: Option<&str>) -> Result<()> {
+	let input_dir = Path::new("input");
+
+	let until = SnapshotAt::parse(args.first().map(String::as_str).unwrap_or_default())?;
+
+	for settings in read_setting(input_dir, false, job)? {
+		let output = OutputNaming::new(Path::new("output"), &settings)?;
+
+		let pal_vec = PaletteVec::new(input_dir, settings.palette_code)?;
+
+		let logs = extract_log(input_dir, &settings.canvas_code)?;
+
+		save_snapshot(&logs, input_dir, &output, &settings, &pal_vec, &until)?;
+	}
+
+	Ok(())
+}
+
+/// `diff <from> <to>`: what changed on the whole canvas between two points
+fn diff_run(args: &[String], job: Option<&str>) -> Result<()> {
+	let input_dir = Path::new("input");
+
+	let [from, to] = args else {
+		return Err(anyhow!("Expect two timestamps or line numbers"));
+	};
+	let (from, to) = (SnapshotAt::parse(from)?, SnapshotAt::parse(to)?);
+
+	for settings in read_setting(input_dir, false, job)? {
+		let output = OutputNaming::new(Path::new("output"), &settings)?;
+
+		let pal_vec = PaletteVec::new(input_dir, settings.palette_code)?;
+
+		let logs = extract_log(input_dir, &settings.canvas_code)?;
+
+		save_snapshot_diff(&logs, input_dir, &output, &settings, &pal_vec, &from, &to)?;
+	}
+
+	Ok(())
+}
+
+/// `canvas`: stats of every user, no key needed
+fn canvas_run(job: Option<&str>) -> Result<()> {
+	let input_dir = Path::new("input");
+
+	for settings in read_setting(input_dir, false, job)? {
+		let output = OutputNaming::new(Path::new("output"), &settings)?;
+
+		let pal_vec = PaletteVec::new(input_dir, settings.palette_code)?;
+
+		let logs = extract_log(input_dir, &settings.canvas_code)?;
+
+		save_canvas_report(&logs, input_dir, &output, &settings, &pal_vec)?;
+	}
 
 	Ok(())
 }
 
 fn create_user_stats(
 	output_info: OutputInfo,
-	full_set_setting: Settings,
-	output_dir: &Path,
-	pal_vec: PaletteVec,
+	full_set_setting: &Settings,
+	region: Option<&Region>,
+	output: &OutputNaming,
+	pal_vec: &PaletteVec,
+	images: &[PathBuf],
+	templates: &[ReportTemplate],
 ) -> Result<()> {
-	let Settings {
-		name, canvas_code, ..
-	} = full_set_setting;
-	let OutputInfo {
-		pixels,
-		undo,
-		replaced,
-		survived,
-		diff_pos_place,
-		diff_pos_undo,
-		color_used,
-		mut pix_place,
-	} = output_info;
+	let Settings { name, .. } = full_set_setting;
 
 	info!("Creating user stats...");
 
-	let mut sort_color = color_used.to_vec();
-	sort_color.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-
-	let sort_string: String =
-		sort_color
-			.iter()
-			.enumerate()
-			.fold(String::new(), |mut v, (rank, (a, b))| {
-				writeln!(
-					&mut v,
-					"{}\t{}\t{:.4}\t{}",
-					rank + 1,
-					b,
-					*b as f32 / pixels as f32 * 100.0,
-					pal_vec.info[*a as usize].name
-				)
-				.unwrap();
-				v
-			});
+	let report = StatsReport::new(&output_info, full_set_setting, region, pal_vec);
 
-	let total_place = pix_place.len() * 25;
-	let to_pix_place = pix_place.drain(..).fold(
-		String::with_capacity(total_place),
-		|mut pix_str, PixelInfoAt { at, pixel, x, y }| {
-			let name = pal_vec.info[pixel as usize].name.to_string();
-			writeln!(&mut pix_str, "{at}\t{x}\t{y}\t{name}").unwrap();
-			pix_str
-		},
-	);
+	let region_name = region.map_or("", |region| region.name.as_str());
+	let file_name = |naming: &str| output.path(Some(name), &format!("{region_name} {naming}"));
+	let stats_name =
+		|naming: &str| output.kind_path("Stats", Some(name), &format!("{region_name} {naming}"));
+	fs::write(stats_name(".txt")?, report.to_text())?;
+	fs::write(stats_name(".json")?, serde_json::to_string_pretty(&report)?)?;
 
-	let make_string = format!(
-		"Canvas: {}\nUsers: {}\nPixels: {}\nSurvivor: {}\nUndo: {}\nReplace: {}\n\nDifferent Position\nPlace: {}\nUndo: {}\n\nTop Color:\nPlace\tUsed\tPercent\tColor\n{}\n\nPlace\tX\tY\tColor\n{}",
-		canvas_code,
-		name,
-		pixels,
-		survived,
-		undo,
-		replaced,
-		diff_pos_place,
-		diff_pos_undo,
-		sort_string,
-		to_pix_place
-	);
+	if !templates.is_empty() {
+		let data = serde_json::to_value(&report)?;
+		for template in templates {
+			fs::write(stats_name(&template.file_name)?, template.render(&data)?)?;
+		}
+	}
 
-	let stats_file_name = output_dir.join(format!("C{canvas_code} Stats {name}.txt"));
-	fs::write(stats_file_name, make_string)?;
+	fs::write(
+		file_name("Transition.csv")?,
+		output_info.transition.to_csv(pal_vec),
+	)?;
+	let transition_png = file_name("Transition.png")?;
+	transition_grid(&output_info.transition, pal_vec).save(&transition_png)?;
+
+	// Sibling files, the page can be opened offline from the output folder
+	let image_names = images
+		.iter()
+		.chain([&transition_png])
+		.filter_map(|path| path.file_name())
+		.map(|file_name| file_name.to_string_lossy().into_owned())
+		.collect::<Vec<_>>();
+	fs::write(
+		stats_name(".html")?,
+		stats_html(&report, pal_vec, &image_names),
+	)?;
 
 	info!("Saved user stats.");
 	Ok(())
@@ -152,102 +342,89 @@ fn extract_log(input_dir: &Path, canvas_code: &str) -> Result<String> {
 	Ok(logs)
 }
 
+/// Whole canvas, or cropped to the region
 fn save_img_collection(
-	image_collection: &mut ImageCollection,
-	output_dir: &Path,
+	image_collection: &ImageCollection,
+	output: &OutputNaming,
 	Settings {
-		name,
-		canvas_code,
-		frame_delay,
-		..
+		name, team, svg, ..
 	}: &Settings,
+	region: Option<&Region>,
 	pal_vec: &PaletteVec,
-) -> Result<()> {
-	let blank = pal_vec.blank_index;
-	let format_name =
-		|naming: &str| -> PathBuf { output_dir.join(format!("C{canvas_code} {name} {naming}")) };
-
-	{
-		let palette = pal_vec.expand_palette();
+) -> Result<Vec<PathBuf>> {
+	let format_name = |naming: &str| match region {
+		Some(region) => output.path(Some(name), &format!("{} {naming}", region.name)),
+		None => output.path(Some(name), naming),
+	};
+	let label = region.map_or_else(String::new, |region| format!("region {} ", region.name));
+	let mut saved = Vec::with_capacity(12);
 
-		info!("Saving placemap...");
+	let palette = pal_vec.expand_palette();
 
-		image_collection
-			.place
-			.save_in_color(&palette, format_name("Placemap.png"))?;
+	info!("Saving {}placemap...", label);
 
-		image_collection
-			.undo
-			.save_in_color(&palette, format_name("Placemap Undo.png"))?;
-		image_collection
-			.survivor
-			.save_in_color(&palette, format_name("Placemap Survivor.png"))?;
+	for (placemap, naming) in image_collection.placemaps() {
+		let path = format_name(&naming)?;
+		crop_to(region, placemap).save_in_color(&palette, path.clone())?;
+		saved.push(path);
+	}
 
-		info!("Saved placemap.");
+	if *svg {
+		for (placemap, naming) in image_collection.vector_maps() {
+			fs::write(
+				format_name(naming)?,
+				placemap_svg(&crop_to(region, placemap), pal_vec),
+			)?;
+		}
 	}
 
-	info!("Encoding animated placemap.");
+	for (count_img, naming) in image_collection.heatmaps() {
+		let path = format_name(naming)?;
+		count_heatmap(&crop_to(region, count_img)).save(&path)?;
+		saved.push(path);
+	}
 
-	let flat_palette = pal_vec.flat_palette();
-	let total_frame = image_collection.gif.len() + 2;
-	let mut gif_ready: Vec<Frame> = Vec::with_capacity(total_frame);
-
-	image_collection
-		.gif
-		.drain(..)
-		.for_each(|GrayImageCropped { left, top, img }| {
-			let width = img.width() as u16;
-			let height = img.height() as u16;
-			let buff = img.to_vec();
-
-			let frame = Frame {
-				delay: *frame_delay,
-				dispose: DisposalMethod::Any,
-				transparent: Some(blank),
-				left,
-				top,
-				width,
-				height,
-				palette: Some(flat_palette.clone()),
-				buffer: buff.into(),
-				..Default::default()
-			};
+	let path = format_name("Placemap Age.png")?;
+	age_gradient(&crop_to(region, &image_collection.place_age)).save(&path)?;
+	saved.push(path);
 
-			gif_ready.push(frame);
-		});
+	if let Some(TeamSetting { members, .. }) = team {
+		let path = format_name("Placemap Team.png")?;
+		attribution_map(&crop_to(region, &image_collection.member), members.len()).save(&path)?;
+		saved.push(path);
+	}
 
-	let global_palette = &pal_vec.flat_palette();
-	let width = image_collection.place.width() as u16;
-	let height = image_collection.place.height() as u16;
+	info!("Saved {}placemap.", label);
 
-	let mut gif_file = File::create(format_name("Placemap Gif.gif"))?;
-	let mut gif_encoder = gif::Encoder::new(&mut gif_file, width, height, global_palette)?;
-	gif_encoder.set_repeat(Repeat::Finite(1))?;
+	Ok(saved)
+}
 
-	for frame in gif_ready.into_iter() {
-		gif_encoder.write_frame(&frame)?;
+fn crop_to<'a, P: Pixel + 'static>(
+	region: Option<&Region>,
+	img: &'a ImageBuffer<P, Vec<P::Subpixel>>,
+) -> Cow<'a, ImageBuffer<P, Vec<P::Subpixel>>> {
+	match region {
+		Some(region) => Cow::Owned(region.crop_from(img)),
+		None => Cow::Borrowed(img),
 	}
-
-	info!("Encoded animated placemap.");
-
-	Ok(())
 }
 
 fn process_place_map(
-	input_dir: &Path,
+	logs: &str,
 	pal_vec: &PaletteVec,
-	Settings {
-		user_key,
-		pix_th,
-		pix_per_frame,
-		canvas_code,
-		..
-	}: &Settings,
+	settings: &Settings,
+	region: Option<&Region>,
 	ImageCollection {
 		place: img_placed,
 		undo: img_undo,
 		survivor: img_survivor,
-		gif: img_gif,
+		kind: img_kind,
+		owner: img_owner,
+		undo_count: img_undo_count,
+		place_count: img_place_count,
+		place_age: img_place_age,
+		member: img_member,
+		..
 	}: &mut ImageCollection,
 	OutputInfo {
 		pixels,
@@ -257,90 +434,249 @@ fn process_place_map(
 		diff_pos_place,
 		diff_pos_undo,
 		color_used,
-		pix_place,
+		milestones,
+		first,
+		last,
+		timeline,
+		transition,
+		place_kind,
+		most_placed,
+		most_undone,
+		members: member_info,
 	}: &mut OutputInfo,
+	mut animation: Option<&mut AnimationStream>,
 ) -> Result<()> {
-	let logs = extract_log(input_dir, canvas_code)?;
+	let Settings {
+		pix_th,
+		pix_per_frame,
+		milestones: milestone_setting,
+		age_by,
+		..
+	} = settings;
 	let logs_queue = logs.trim().split('\n');
 	let blank = pal_vec.blank_index;
+	let members = settings.members();
 
-	let mut active_pix = blank;
-	let mut old_pix = Luma([0]);
-	let mut prev_lived_color = Luma([0]);
 	let mut prev_process_frame = img_placed.clone();
 	let mut process_frame = img_placed.clone();
 	let mut last_saved_frame_backup = img_placed.clone();
 	let mut last_saved_frame = img_placed.clone();
 
-	// xy : color
-	let mut vec_survivor_pix: HashMap<(u32, u32), Luma<u8>> = HashMap::new();
+	let mut first_seconds = None;
+	let mut prev_last: Option<PixelInfoAt> = None;
+	let mut first_placed = FirstPlaced::default();
+	// Last placement of each member
+	let mut undo_state: Vec<Option<PlaceUndo>> = members.iter().map(|_| None).collect();
+	// Member of the latest placement, frames and milestones only roll back for it
+	let mut latest_member = None;
+
+	// xy : (color, member)
+	let mut vec_survivor_pix: HashMap<(u32, u32), (Luma<u8>, Luma<u8>)> = HashMap::new();
+	// xy : owner
+	let mut vec_owner_pix: HashMap<(u32, u32), Luma<u8>> = HashMap::new();
+	// xy of survivor placed outside the window
+	let mut out_window_pix: HashSet<(u32, u32)> = HashSet::new();
 
 	info!("Processing logs...");
 
 	for (at, lines) in logs_queue.into_iter().enumerate() {
-		let splited: Vec<&str> = lines.split('\t').collect();
-		let [date, rand_hash, x, y, color_index, action] = splited[..] else {
-			error!("Invalid at line {}: {:?}", at, splited);
+		let Some(log) = LogLine::parse(lines) else {
+			error!("Invalid at line {}: {:?}", at, lines);
 			continue;
 		};
-
-		let digest_format = [date, x, y, color_index, user_key].join(",");
-		let digested = digest(digest_format);
-
-		let (x, y) = (x.parse()?, y.parse()?);
+		let LogLine {
+			date,
+			x,
+			y,
+			color: indexed,
+			undo: is_undo,
+			..
+		} = log;
+
+		if region.is_some_and(|region| !region.contains(x, y)) {
+			continue;
+		}
 
 		// Not The Key Owner
-		if digested.encode_utf16().ne(rand_hash.encode_utf16()) {
-			if action == "user undo" {
-				let Some(old_survivor) = vec_survivor_pix.remove(&(x, y)) else {
+		let Some(member) = log.member(&members) else {
+			if is_undo {
+				if let Some(old_owner) = vec_owner_pix.remove(&(x, y)) {
+					img_owner.put_pixel(x, y, old_owner);
+				}
+				let Some((old_survivor, old_member)) = vec_survivor_pix.remove(&(x, y)) else {
 					continue;
 				};
 				img_survivor.put_pixel(x, y, old_survivor);
+				img_member.put_pixel(x, y, old_member);
 				continue;
 			}
+			vec_owner_pix.insert((x, y), *img_owner.get_pixel(x, y));
+			img_owner.put_pixel(x, y, PlaceKind::OverOther.owner());
 			let old_survivor = img_survivor.get_pixel(x, y);
-			vec_survivor_pix.insert((x, y), *old_survivor);
+			let old_member = img_member.get_pixel(x, y);
+			vec_survivor_pix.insert((x, y), (*old_survivor, *old_member));
 			img_survivor.put_pixel(x, y, Luma([blank]));
+			img_member.put_pixel(x, y, Luma([NO_MEMBER]));
 			continue;
-		}
+		};
 
-		let indexed: u8 = color_index.parse()?;
 		let luma = Luma([indexed]);
 
-		if action == "user undo" {
-			pix_th.contains(pixels).then(|| pix_place.pop());
-			if *pixels % pix_per_frame == 0 {
-				last_saved_frame = last_saved_frame_backup.clone();
-				process_frame = prev_process_frame.clone();
-				img_gif.pop();
+		if is_undo {
+			let Some(PlaceUndo {
+				color,
+				placed,
+				survivor,
+				owner,
+				member: prev_member,
+				kind,
+				kind_pix,
+				age,
+				date: placed_date,
+				in_window,
+				out_window,
+			}) = undo_state[member].take()
+			else {
+				continue;
+			};
+			let is_latest = latest_member == Some(member);
+			if is_latest {
+				latest_member = None;
+			}
+
+			img_owner.put_pixel(x, y, owner);
+			img_survivor.put_pixel(x, y, survivor);
+			img_member.put_pixel(x, y, prev_member);
+			if out_window {
+				out_window_pix.insert((x, y));
+			} else {
+				out_window_pix.remove(&(x, y));
+			}
+
+			// Undone placement was outside the window
+			if !in_window {
+				continue;
+			}
+
+			// Team member undo after another member placed keep the sequence
+			if is_latest {
+				if milestones
+					.last()
+					.is_some_and(|milestone| milestone.info.at == *pixels)
+				{
+					milestones.pop();
+				}
+				if let Some(animation) = animation.as_mut()
+					&& *pixels % pix_per_frame == 0
+				{
+					last_saved_frame = last_saved_frame_backup.clone();
+					process_frame = prev_process_frame.clone();
+					animation.pop();
+				}
+				*last = prev_last.take();
+			}
+			if survivor.0[0] != blank {
+				*replaced -= 1;
+				transition.sub_used(survivor.0[0], color);
 			}
-			(old_pix.0[0] != blank).then(|| *replaced -= 1);
-			color_used.sub_used(&active_pix);
+			place_kind[kind as usize] -= 1;
+			img_kind[kind as usize].put_pixel(x, y, kind_pix);
+			color_used.sub_used(&color);
 			*pixels -= 1;
 			*undo += 1;
+			member_info[member].pixels -= 1;
+			member_info[member].undo += 1;
+
+			timeline.sub_placed(&placed_date);
+			first_placed.undo(member);
+			*first = first_placed.first();
+
+			img_placed.put_pixel(x, y, placed);
+			process_frame.put_pixel(x, y, placed);
+			img_undo.put_pixel(x, y, Luma([color]));
+			img_undo_count.get_pixel_mut(x, y).0[0] += 1;
+			img_place_count.get_pixel_mut(x, y).0[0] -= 1;
+			img_place_age.put_pixel(x, y, age);
+			continue;
+		}
 
-			img_placed.put_pixel(x, y, old_pix);
-			process_frame.put_pixel(x, y, old_pix);
-			img_survivor.put_pixel(x, y, prev_lived_color);
-			img_undo.put_pixel(x, y, Luma([active_pix]));
+		// Keep previous Cordinate Pixel's [Color] before apply
+		let prev_lived_color = *img_survivor.get_pixel(x, y);
+		let prev_owner = *img_owner.get_pixel(x, y);
+		let mut state = PlaceUndo {
+			color: indexed,
+			placed: *img_placed.get_pixel(x, y),
+			survivor: prev_lived_color,
+			owner: prev_owner,
+			member: *img_member.get_pixel(x, y),
+			kind: PlaceKind::from_owner(prev_owner),
+			kind_pix: Luma([0]),
+			age: Luma([0]),
+			date: date.to_owned(),
+			in_window: settings.in_window(date),
+			out_window: out_window_pix.contains(&(x, y)),
+		};
+		img_survivor.put_pixel(x, y, luma);
+		img_owner.put_pixel(x, y, PlaceKind::OverSelf.owner());
+		img_member.put_pixel(x, y, Luma([member as u8]));
+		latest_member = Some(member);
+
+		// Outside the window only keep the survivor state
+		if !state.in_window {
+			first_placed.place(member, None);
+			out_window_pix.insert((x, y));
+			undo_state[member] = Some(state);
 			continue;
 		}
+		out_window_pix.remove(&(x, y));
 
-		old_pix = *img_placed.get_pixel(x, y);
+		if prev_lived_color.0[0] != blank {
+			*replaced += 1;
+			transition.add_used(prev_lived_color.0[0], indexed);
+		}
+
+		let last_kind = state.kind;
+		place_kind[last_kind as usize] += 1;
+		state.kind_pix = *img_kind[last_kind as usize].get_pixel(x, y);
+		img_kind[last_kind as usize].put_pixel(x, y, luma);
 
-		active_pix = indexed as u8;
-		color_used.add_used(&active_pix);
+		color_used.add_used(&indexed);
 
 		img_placed.put_pixel(x, y, luma);
+		img_place_count.get_pixel_mut(x, y).0[0] += 1;
 		process_frame.put_pixel(x, y, luma);
 
-		// Keep previous Cordinate Pixel's [Color] before apply
-		prev_lived_color = *img_survivor.get_pixel(x, y);
-		(prev_lived_color.0[0] != blank).then(|| *replaced += 1);
-		img_survivor.put_pixel(x, y, luma);
-
 		*pixels += 1;
-		if *pixels % pix_per_frame == 0 {
+		member_info[member].pixels += 1;
+
+		let pixel_info = PixelInfoAt {
+			at: *pixels,
+			date: date.to_owned(),
+			pixel: indexed,
+			x,
+			y,
+		};
+		timeline.add_placed(date);
+
+		let age = match age_by {
+			AgeBy::Order => *pixels,
+			AgeBy::Time => {
+				let seconds = date_seconds(date).unwrap_or_default();
+				let first_seconds = *first_seconds.get_or_insert(seconds);
+				(seconds - first_seconds).max(0) as u32 + 1
+			},
+		};
+		state.age = *img_place_age.get_pixel(x, y);
+		img_place_age.put_pixel(x, y, Luma([age]));
+		undo_state[member] = Some(state);
+
+		first_placed.place(member, Some(&pixel_info));
+		*first = first_placed.first();
+		prev_last = last.replace(pixel_info.clone());
+
+		if let Some(animation) = animation.as_mut()
+			&& *pixels % pix_per_frame == 0
+		{
 			prev_process_frame = process_frame.clone();
 			let mut uncrop = process_frame.clone();
 			process_frame.fill(blank);
@@ -355,54 +691,170 @@ fn process_place_map(
 				cropped.left as i64,
 				cropped.top as i64,
 			);
-			img_gif.push(cropped);
+			animation.push(AnimationFrame {
+				img: cropped,
+				pixels: *pixels,
+				date: date.to_owned(),
+			})?;
 		}
 
-		if pix_th.contains(pixels) {
-			pix_place.push(PixelInfoAt {
-				at: *pixels,
-				pixel: indexed,
-				x,
-				y,
+		let MilestoneSetting {
+			every,
+			first_of_day,
+			first_color,
+		} = milestone_setting;
+		let kinds: Vec<MilestoneKind> = [
+			(pix_th.contains(pixels), MilestoneKind::Threshold),
+			(
+				every.is_some_and(|every| every != 0 && *pixels % every == 0),
+				MilestoneKind::Every,
+			),
+			(
+				*first_of_day && timeline.placed_on_day(date) == 1,
+				MilestoneKind::FirstOfDay,
+			),
+			(
+				*first_color && color_used.used(&indexed) == 1,
+				MilestoneKind::FirstColor,
+			),
+		]
+		.into_iter()
+		.filter_map(|(reached, kind)| reached.then_some(kind))
+		.collect();
+		if !kinds.is_empty() {
+			milestones.push(Milestone {
+				info: pixel_info,
+				kinds,
 			});
 		}
 	}
 
-	if *pixels % pix_per_frame != 0 {
+	if let Some(animation) = animation
+		&& *pixels % pix_per_frame != 0
+	{
 		let mut uncrop = process_frame;
 		let (left, top) = uncrop.crop_in(blank);
 		let mut cropped = GrayImageCropped::new(left, top, uncrop);
 		cropped.diff_out_self(&last_saved_frame, blank);
 		cropped.crop_in(blank);
-		img_gif.push(cropped);
+		animation.push(AnimationFrame {
+			img: cropped,
+			pixels: *pixels,
+			date: last
+				.as_ref()
+				.map(|info| info.date.clone())
+				.unwrap_or_default(),
+		})?;
 	}
 
 	info!("Processed logs.");
 
+	for (x, y) in out_window_pix {
+		img_survivor.put_pixel(x, y, Luma([blank]));
+		img_member.put_pixel(x, y, Luma([NO_MEMBER]));
+	}
+
+	for (index, info) in member_info.iter_mut().enumerate() {
+		info.survived = img_member
+			.pixels()
+			.filter(|px| px.0[0] == index as u8)
+			.count();
+	}
+
 	let count_visible_pixel =
 		|imaged: &GrayImage| -> usize { imaged.pixels().filter(|x| x.0[0] != blank).count() };
 
 	*survived = count_visible_pixel(img_survivor);
 	*diff_pos_place = count_visible_pixel(img_placed);
 	*diff_pos_undo = count_visible_pixel(img_undo);
+	*most_placed = count_max(img_place_count);
+	*most_undone = count_max(img_undo_count);
 
 	Ok(())
 }
 
+/// User pixels in the window after undo
+fn count_user_pixels(logs: &str, settings: &Settings) -> u32 {
+	let members = settings.members();
+	let mut pixels: u32 = 0;
+	for lines in logs.trim().split('\n') {
+		let Some(log) = LogLine::parse(lines) else {
+			continue;
+		};
+		if !settings.in_window(log.date) || log.member(&members).is_none() {
+			continue;
+		}
+		if log.undo {
+			pixels = pixels.saturating_sub(1);
+		} else {
+			pixels += 1;
+		}
+	}
+	pixels
+}
+
 fn intial_img(input_dir: &Path, canvas_code: &str, pixel: u8) -> Result<ImageCollection> {
 	let img_path = input_dir.join(format!("canvas-{canvas_code}-initial.png"));
-	let (width, height) = image::open(img_path)?.dimensions();
+	let initial_canvas = image::open(img_path)?;
+	let (width, height) = initial_canvas.dimensions();
 
-	let img_collection = ImageCollection::new_size(width, height, pixel);
+	let img_collection =
+		ImageCollection::new_size(width, height, pixel).with_initial_art(&initial_canvas);
 
 	info!("Intial Image ready");
 
 	Ok(img_collection)
 }
 
-fn read_setting() -> Result<Settings> {
-	let bytes_read = fs::read("settings.ron")?;
-	let settings = ron::de::from_bytes(&bytes_read)?;
+/// `with_keys`: user stats, not only the whole canvas. `job`: only this job of a job list
+fn read_setting(input_dir: &Path, with_keys: bool, job: Option<&str>) -> Result<Vec<Settings>> {
+	let bytes_read = fs::read_to_string("settings.ron").map_err(|err| {
+		anyhow!("settings.ron: {err}, run `pxls-placemaper example > settings.ron`")
+	})?;
+	let mut all_settings = if is_job_list(&bytes_read) {
+		// `svg: true` instead of `svg: Some(true)` in job options
+		let jobs: Jobs = ron::Options::default()
+			.with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+			.from_str(&bytes_read)
+			.map_err(settings_error)?;
+		jobs.to_settings(job, with_keys)?
+	} else if let Some(job) = job {
+		return Err(anyhow!("No job {:?}, settings.ron is not a job list", job));
+	} else {
+		let settings: Settings = ron::de::from_str(&bytes_read).map_err(settings_error)?;
+		vec![(String::new(), settings)]
+	};
+
+	for (job, settings) in all_settings.iter_mut() {
+		let checked = settings
+			.resolve_keys(with_keys)
+			.and_then(|_| settings.validate(input_dir, with_keys));
+		match checked {
+			Err(err) if !job.is_empty() => {
+				return Err(anyhow!("Job {} {}: {}", job, settings.name, err));
+			},
+			checked => checked?,
+		}
+	}
 	info!("Complete reading Setting.");
-	Ok(settings)
+	Ok(all_settings
+		.into_iter()
+		.map(|(_, settings)| settings)
+		.collect())
+}
+
+fn settings_error(
+	ron::error::SpannedError { code, position }: ron::error::SpannedError,
+) -> anyhow::Error {
+	let hint = match code {
+		ron::Error::MissingStructField { .. } | ron::Error::ExpectedNamedStructLike(_) => {
+			"\nRun `pxls-placemaper example` for every field with comments"
+		},
+		_ => "",
+	};
+	anyhow!(
+		"settings.ron line {}, column {}: {code}{hint}",
+		position.line,
+		position.col
+	)
 }
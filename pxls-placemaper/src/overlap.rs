@@ -0,0 +1,211 @@
+use anyhow::Result;
+use image::{GrayImage, Luma, Rgb, Rgba, RgbaImage};
+use log::{error, info};
+use std::{collections::HashMap, fs};
+
+use crate::{render::member_color, report::OverlapReport, structure::*};
+
+/// Last placer at each position
+const NOBODY: u8 = 0;
+const USER_A: u8 = 1;
+const USER_B: u8 = 2;
+const OTHER: u8 = 3;
+
+/// Positions, overwrites and colors of two users against each other
+pub struct OverlapInfo {
+	/// Placed count of A and B at each position
+	pub touched: [CountImage; 2],
+	/// A over B, B over A
+	pub overwrote: [u32; 2],
+	/// Color index : placed by A and B
+	pub colors: HashMap<u8, [u32; 2]>,
+}
+
+/// One placement, put back on undo
+struct OverlapUndo {
+	prev_owner: Luma<u8>,
+	color: u8,
+	counted: bool,
+	overwrote: bool,
+}
+
+impl OverlapInfo {
+	pub fn new(width: u32, height: u32) -> Self {
+		Self {
+			touched: std::array::from_fn(|_| CountImage::new(width, height)),
+			overwrote: [0; 2],
+			colors: HashMap::new(),
+		}
+	}
+
+	/// Touched by A only, B only, both
+	pub fn position_counts(&self) -> [usize; 3] {
+		let [a, b] = &self.touched;
+		let mut counts = [0; 3];
+		for (px_a, px_b) in a.pixels().zip(b.pixels()) {
+			match (px_a.0[0] != 0, px_b.0[0] != 0) {
+				(true, false) => counts[0] += 1,
+				(false, true) => counts[1] += 1,
+				(true, true) => counts[2] += 1,
+				(false, false) => {},
+			}
+		}
+		counts
+	}
+
+	/// A only, B only in user color, both in yellow
+	pub fn overlap_map(&self) -> RgbaImage {
+		let [a, b] = &self.touched;
+		let both = Rgb([240, 200, 40]);
+		RgbaImage::from_fn(a.width(), a.height(), |x, y| {
+			let Rgb([r, g, blue]) = match (a.get_pixel(x, y).0[0] != 0, b.get_pixel(x, y).0[0] != 0)
+			{
+				(true, false) => member_color(0, 2),
+				(false, true) => member_color(1, 2),
+				(true, true) => both,
+				(false, false) => return Rgba([0, 0, 0, 0]),
+			};
+			Rgba([r, g, blue, 255])
+		})
+	}
+}
+
+/// Replay the logs tracking who placed last at each position
+pub fn process_overlap(
+	logs: &str,
+	settings: &Settings,
+	OverlapSetting {
+		user_key: other_key,
+		..
+	}: &OverlapSetting,
+	width: u32,
+	height: u32,
+) -> Result<OverlapInfo> {
+	let members = settings.members();
+	let mut info = OverlapInfo::new(width, height);
+	let mut img_owner = GrayImage::from_pixel(width, height, Luma([NOBODY]));
+	// xy : owner, for undo of other users
+	let mut vec_owner_pix: HashMap<(u32, u32), Luma<u8>> = HashMap::new();
+	// Last placement of A and B
+	let mut undo_state: [Option<OverlapUndo>; 2] = [None, None];
+
+	info!("Processing overlap...");
+
+	for (at, lines) in logs.trim().split('\n').enumerate() {
+		let Some(log) = LogLine::parse(lines) else {
+			error!("Invalid at line {}: {:?}", at, lines);
+			continue;
+		};
+		let LogLine {
+			date,
+			x,
+			y,
+			color,
+			undo: is_undo,
+			..
+		} = log;
+		if x >= width || y >= height {
+			continue;
+		}
+
+		let user = if log.member(&members).is_some() {
+			USER_A
+		} else if log.is_key_owner(other_key.as_str()) {
+			USER_B
+		} else {
+			OTHER
+		};
+
+		if user == OTHER {
+			if is_undo {
+				if let Some(old_owner) = vec_owner_pix.remove(&(x, y)) {
+					img_owner.put_pixel(x, y, old_owner);
+				}
+				continue;
+			}
+			vec_owner_pix.insert((x, y), *img_owner.get_pixel(x, y));
+			img_owner.put_pixel(x, y, Luma([OTHER]));
+			continue;
+		}
+
+		let side = (user - USER_A) as usize;
+
+		if is_undo {
+			let Some(OverlapUndo {
+				prev_owner,
+				color,
+				counted,
+				overwrote,
+			}) = undo_state[side].take()
+			else {
+				continue;
+			};
+			img_owner.put_pixel(x, y, prev_owner);
+			if !counted {
+				continue;
+			}
+			info.touched[side].get_pixel_mut(x, y).0[0] -= 1;
+			if let Some(used) = info.colors.get_mut(&color) {
+				used[side] -= 1;
+			}
+			if overwrote {
+				info.overwrote[side] -= 1;
+			}
+			continue;
+		}
+
+		let prev_owner = *img_owner.get_pixel(x, y);
+		let counted = settings.in_window(date);
+		let rival = if user == USER_A { USER_B } else { USER_A };
+		let overwrote = counted && prev_owner.0[0] == rival;
+
+		img_owner.put_pixel(x, y, Luma([user]));
+		if counted {
+			info.touched[side].get_pixel_mut(x, y).0[0] += 1;
+			info.colors.entry(color).or_default()[side] += 1;
+			if overwrote {
+				info.overwrote[side] += 1;
+			}
+		}
+		undo_state[side] = Some(OverlapUndo {
+			prev_owner,
+			color,
+			counted,
+			overwrote,
+		});
+	}
+
+	info!("Processed overlap.");
+	Ok(info)
+}
+
+/// Overlap report in text and JSON, overlap map
+pub fn save_overlap_report(
+	logs: &str,
+	output: &OutputNaming,
+	settings: &Settings,
+	overlap_setting: &OverlapSetting,
+	pal_vec: &PaletteVec,
+	(width, height): (u32, u32),
+) -> Result<()> {
+	let info = process_overlap(logs, settings, overlap_setting, width, height)?;
+
+	let report = OverlapReport::new(&info, settings, overlap_setting, pal_vec);
+
+	let file_name = |naming: &str| {
+		output.kind_path(
+			"Overlap",
+			Some(&settings.name),
+			&format!("{} {naming}", overlap_setting.name),
+		)
+	};
+	fs::write(file_name("Stats.txt")?, report.to_text())?;
+	fs::write(
+		file_name("Stats.json")?,
+		serde_json::to_string_pretty(&report)?,
+	)?;
+	info.overlap_map().save(file_name("Map.png")?)?;
+
+	info!("Saved overlap report.");
+	Ok(())
+}
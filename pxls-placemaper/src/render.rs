@@ -0,0 +1,277 @@
+use image::{GrayImage, Luma, Rgb, RgbImage, Rgba, RgbaImage, imageops::overlay};
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use crate::{font::*, structure::*};
+
+const CELL_SIZE: u32 = 16;
+const EMPTY_CELL: Rgb<u8> = Rgb([32, 32, 32]);
+
+/// Black, Red, Yellow, White ramp of 0.0 ..= 1.0
+pub fn heat_color(ratio: f32) -> Rgb<u8> {
+	let ratio = ratio.clamp(0.0, 1.0) * 3.0;
+	let channel = |from: f32| ((ratio - from).clamp(0.0, 1.0) * 255.0) as u8;
+	Rgb([channel(0.0), channel(1.0), channel(2.0)])
+}
+
+/// Palette swatches on top (placed) and left (replaced), square root scale.
+/// Swatches show the palette index, names on the left
+pub fn transition_grid(transition: &ColorTransition, pal_vec: &PaletteVec) -> RgbImage {
+	let colors = pal_vec.info.len() as u32;
+	let side = (colors + 1) * CELL_SIZE;
+	let max = transition.max().max(1) as f32;
+
+	let grid = RgbImage::from_fn(side, side, |x, y| {
+		let (col, row) = (x / CELL_SIZE, y / CELL_SIZE);
+		let on_border = x % CELL_SIZE == 0 || y % CELL_SIZE == 0;
+		match (col, row) {
+			(0, 0) => Rgb([0, 0, 0]),
+			(_, _) if on_border => Rgb([0, 0, 0]),
+			(col, 0) => swatch(pal_vec, col - 1),
+			(0, row) => swatch(pal_vec, row - 1),
+			(col, row) => match transition.get(row as u8 - 1, col as u8 - 1) {
+				..=0 => EMPTY_CELL,
+				count => heat_color((count as f32 / max).sqrt()),
+			},
+		}
+	});
+
+	let pad = 4;
+	let white = Rgb([255, 255, 255]);
+	let names = pal_vec
+		.info
+		.iter()
+		.enumerate()
+		.map(|(index, info)| format!("{index} {}", info.name))
+		.collect::<Vec<_>>();
+	let label_width = names
+		.iter()
+		.map(String::as_str)
+		.chain(["Replaced"])
+		.map(|text| text_size(text, 1).0)
+		.max()
+		.unwrap_or(0)
+		+ pad * 2;
+	let title_height = GLYPH_HEIGHT + pad * 2;
+
+	let mut labelled = RgbImage::new(label_width + side, title_height + side);
+	overlay(
+		&mut labelled,
+		&grid,
+		label_width as i64,
+		title_height as i64,
+	);
+
+	let text_top = (CELL_SIZE - GLYPH_HEIGHT).div_ceil(2);
+	draw_label(&mut labelled, label_width + CELL_SIZE, pad, "Placed", white);
+	draw_label(
+		&mut labelled,
+		pad,
+		title_height + text_top,
+		"Replaced",
+		white,
+	);
+	for (index, name) in names.iter().enumerate() {
+		let at = (index as u32 + 1) * CELL_SIZE;
+		let number = index.to_string();
+		let Rgb([r, g, b]) = swatch(pal_vec, index as u32);
+		// Dark text on light swatch
+		let ink = match r as u32 * 299 + g as u32 * 587 + b as u32 * 114 {
+			..128_000 => white,
+			_ => Rgb([0, 0, 0]),
+		};
+		let text_left = (CELL_SIZE + 1).saturating_sub(text_size(&number, 1).0) / 2;
+		draw_label(
+			&mut labelled,
+			label_width + at + text_left,
+			title_height + text_top,
+			&number,
+			ink,
+		);
+		draw_label(
+			&mut labelled,
+			label_width + text_left,
+			title_height + at + text_top,
+			&number,
+			ink,
+		);
+		draw_label(
+			&mut labelled,
+			pad,
+			title_height + at + text_top,
+			name,
+			white,
+		);
+	}
+	labelled
+}
+
+/// One line of the bitmap font in a color
+fn draw_label(img: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>) {
+	let (width, height) = text_size(text, 1);
+	let mut mask = GrayImage::new(width, height);
+	draw_text(&mut mask, 0, 0, text, 1, 255);
+	for (dx, dy, px) in mask.enumerate_pixels() {
+		if px.0[0] != 0
+			&& let Some(target) = img.get_pixel_mut_checked(x + dx, y + dy)
+		{
+			*target = color;
+		}
+	}
+}
+
+/// Untouched position is transparent, square root scale
+pub fn count_heatmap(count_img: &CountImage) -> RgbaImage {
+	let max = count_img
+		.pixels()
+		.map(|px| px.0[0])
+		.max()
+		.unwrap_or(0)
+		.max(1) as f32;
+	RgbaImage::from_fn(count_img.width(), count_img.height(), |x, y| {
+		match count_img.get_pixel(x, y).0[0] {
+			0 => Rgba([0, 0, 0, 0]),
+			count => {
+				// Keep lowest count visible on black background
+				let Rgb([r, g, b]) = heat_color(0.1 + (count as f32 / max).sqrt() * 0.9);
+				Rgba([r, g, b, 255])
+			},
+		}
+	})
+}
+
+/// Dark purple, Blue, Green, Yellow ramp of 0.0 ..= 1.0, similar to viridis
+pub fn ramp_color(ratio: f32) -> Rgb<u8> {
+	const STOPS: [[f32; 3]; 5] = [
+		[68.0, 1.0, 84.0],
+		[59.0, 82.0, 139.0],
+		[33.0, 145.0, 140.0],
+		[94.0, 201.0, 98.0],
+		[253.0, 231.0, 37.0],
+	];
+	let scaled = ratio.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+	let index = (scaled as usize).min(STOPS.len() - 2);
+	let fraction = scaled - index as f32;
+	let [from, to] = [STOPS[index], STOPS[index + 1]];
+	Rgb(std::array::from_fn(|n| {
+		(from[n] + (to[n] - from[n]) * fraction) as u8
+	}))
+}
+
+/// Empty position is transparent, legend strip of oldest to newest below
+pub fn age_gradient(age_img: &CountImage) -> RgbaImage {
+	let (width, height) = age_img.dimensions();
+	let ages = age_img.pixels().map(|px| px.0[0]).filter(|age| *age != 0);
+	let (min, max) = ages.fold((u32::MAX, 0), |(min, max), age| {
+		(min.min(age), max.max(age))
+	});
+	let range = max.saturating_sub(min).max(1) as f32;
+	let legend_height = (height / 20).max(4);
+
+	RgbaImage::from_fn(width, height + legend_height, |x, y| {
+		let ratio = match age_img.get_pixel_checked(x, y) {
+			None => x as f32 / width.saturating_sub(1).max(1) as f32,
+			Some(Luma([0])) => return Rgba([0, 0, 0, 0]),
+			Some(Luma([age])) => (age - min) as f32 / range,
+		};
+		let Rgb([r, g, b]) = ramp_color(ratio);
+		Rgba([r, g, b, 255])
+	})
+}
+
+/// Evenly spaced hues, one per team member
+pub fn member_color(index: usize, members: usize) -> Rgb<u8> {
+	let hue = index as f32 / members.max(1) as f32 * 6.0;
+	let (chroma, light) = (200.0, 40.0);
+	let channel = |offset: f32| {
+		let distance = ((hue - offset).rem_euclid(6.0) - 3.0).abs();
+		(light + chroma * (distance - 1.0).clamp(0.0, 1.0)) as u8
+	};
+	Rgb([channel(0.0), channel(4.0), channel(2.0)])
+}
+
+/// Survived pixels in member color, others transparent
+pub fn attribution_map(member_img: &GrayImage, members: usize) -> RgbaImage {
+	RgbaImage::from_fn(
+		member_img.width(),
+		member_img.height(),
+		|x, y| match member_img.get_pixel(x, y).0[0] {
+			NO_MEMBER => Rgba([0, 0, 0, 0]),
+			index => {
+				let Rgb([r, g, b]) = member_color(index as usize, members);
+				Rgba([r, g, b, 255])
+			},
+		},
+	)
+}
+
+/// One `rect` per same color block, row runs merged down while the span matches
+pub fn placemap_svg(img: &GrayImage, pal_vec: &PaletteVec) -> String {
+	let (width, height) = img.dimensions();
+	let blank = pal_vec.blank_index;
+
+	// color : x, y, width, height
+	let mut rects: BTreeMap<u8, Vec<[u32; 4]>> = BTreeMap::new();
+	// (x, width, color) : index in rects of block ending on previous row
+	let mut open: BTreeMap<(u32, u32, u8), usize> = BTreeMap::new();
+	for y in 0..height {
+		let mut next_open = BTreeMap::new();
+		let mut x = 0;
+		while x < width {
+			let color = img.get_pixel(x, y).0[0];
+			let mut run = 1;
+			while x + run < width && img.get_pixel(x + run, y).0[0] == color {
+				run += 1;
+			}
+			if color != blank {
+				let color_rects = rects.entry(color).or_default();
+				let index = match open.get(&(x, run, color)) {
+					Some(&index) => {
+						color_rects[index][3] += 1;
+						index
+					},
+					None => {
+						color_rects.push([x, y, run, 1]);
+						color_rects.len() - 1
+					},
+				};
+				next_open.insert((x, run, color), index);
+			}
+			x += run;
+		}
+		open = next_open;
+	}
+
+	let rect_count: usize = rects.values().map(Vec::len).sum();
+	let mut svg = String::with_capacity(rect_count * 40 + 256);
+	let _ = writeln!(
+		&mut svg,
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" shape-rendering=\"crispEdges\">"
+	);
+	for (color, color_rects) in rects {
+		let [r, g, b, _a] = pal_vec.info[color as usize].rgba.0;
+		let _ = writeln!(&mut svg, "<g fill=\"#{r:02X}{g:02X}{b:02X}\">");
+		for [x, y, w, h] in color_rects {
+			let _ = writeln!(
+				&mut svg,
+				"<rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\"/>"
+			);
+		}
+		svg.push_str("</g>\n");
+	}
+	svg.push_str("</svg>\n");
+	svg
+}
+
+/// Position and count of the highest value
+pub fn count_max(count_img: &CountImage) -> Option<(u32, u32, u32)> {
+	count_img
+		.enumerate_pixels()
+		.filter(|(_, _, px)| px.0[0] != 0)
+		.max_by_key(|(_, _, px)| px.0[0])
+		.map(|(x, y, px)| (x, y, px.0[0]))
+}
+
+fn swatch(pal_vec: &PaletteVec, index: u32) -> Rgb<u8> {
+	let [r, g, b, _a] = pal_vec.info[index as usize].rgba.0;
+	Rgb([r, g, b])
+}
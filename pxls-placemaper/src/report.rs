@@ -0,0 +1,550 @@
+use image::Rgb;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+use crate::{overlap::OverlapInfo, render::member_color, snapshot::CanvasReplay, structure::*};
+
+/// User stats ready for text and JSON output
+#[derive(Serialize)]
+pub struct StatsReport {
+	pub canvas: String,
+	pub user: String,
+	pub region: Option<String>,
+	pub start: Option<String>,
+	pub end: Option<String>,
+	pub pixels: u32,
+	pub survived: usize,
+	pub undo: u32,
+	pub replaced: u32,
+	pub diff_pos_place: usize,
+	pub diff_pos_undo: usize,
+	pub most_placed: Option<PositionReport>,
+	pub most_undone: Option<PositionReport>,
+	pub place_kind: Vec<KindReport>,
+	pub first: Option<PixelReport>,
+	pub last: Option<PixelReport>,
+	pub active_days: usize,
+	pub days: Vec<DayReport>,
+	pub longest_streak: Option<StreakReport>,
+	pub busiest_hour: Option<HourReport>,
+	pub colors: Vec<ColorReport>,
+	pub milestones: Vec<MilestoneReport>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub members: Vec<MemberReport>,
+}
+
+#[derive(Serialize)]
+pub struct MemberReport {
+	pub name: String,
+	/// Attribution map color, "#RRGGBB"
+	pub color: String,
+	pub pixels: u32,
+	pub undo: u32,
+	pub survived: usize,
+	pub percent: f32,
+}
+
+#[derive(Serialize)]
+pub struct PositionReport {
+	pub x: u32,
+	pub y: u32,
+	pub times: u32,
+}
+
+#[derive(Serialize)]
+pub struct KindReport {
+	pub kind: &'static str,
+	pub pixels: u32,
+}
+
+#[derive(Serialize)]
+pub struct PixelReport {
+	pub at: u32,
+	pub date: String,
+	pub x: u32,
+	pub y: u32,
+	pub color: String,
+}
+
+#[derive(Serialize)]
+pub struct StreakReport {
+	pub days: u32,
+	pub from: String,
+	pub to: String,
+}
+
+#[derive(Serialize)]
+pub struct DayReport {
+	/// "YYYY-MM-DD"
+	pub day: String,
+	pub pixels: u32,
+}
+
+#[derive(Serialize)]
+pub struct HourReport {
+	/// "YYYY-MM-DD HH"
+	pub hour: String,
+	pub pixels: u32,
+}
+
+#[derive(Serialize)]
+pub struct ColorReport {
+	pub rank: usize,
+	pub name: String,
+	pub used: i32,
+	pub percent: f32,
+}
+
+#[derive(Serialize)]
+pub struct MilestoneReport {
+	#[serde(flatten)]
+	pub pixel: PixelReport,
+	pub kinds: Vec<&'static str>,
+}
+
+impl PixelReport {
+	pub fn new(
+		PixelInfoAt {
+			at,
+			date,
+			pixel,
+			x,
+			y,
+		}: &PixelInfoAt,
+		pal_vec: &PaletteVec,
+	) -> Self {
+		Self {
+			at: *at,
+			date: date.clone(),
+			x: *x,
+			y: *y,
+			color: pal_vec.info[*pixel as usize].name.to_string(),
+		}
+	}
+}
+
+impl ColorReport {
+	/// Most used first, ties by palette order
+	pub fn ranking(color_used: &ColorUsed, total: u32, pal_vec: &PaletteVec) -> Vec<Self> {
+		let mut sort_color = color_used.to_vec();
+		sort_color.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+		let named = sort_color
+			.into_iter()
+			.map(|(index, used)| (pal_vec.info[index as usize].name.to_string(), used));
+		Self::ranked(named, total)
+	}
+
+	/// Already sorted counts
+	pub fn ranked(named: impl Iterator<Item = (String, i32)>, total: u32) -> Vec<Self> {
+		named
+			.enumerate()
+			.map(|(rank, (name, used))| ColorReport {
+				rank: rank + 1,
+				name,
+				used,
+				percent: used as f32 / total as f32 * 100.0,
+			})
+			.collect()
+	}
+
+	fn write_table(colors: &[Self], text: &mut String) {
+		text.push_str("Place\tUsed\tPercent\tColor\n");
+		for ColorReport {
+			rank,
+			name,
+			used,
+			percent,
+		} in colors.iter()
+		{
+			let _ = writeln!(text, "{rank}\t{used}\t{percent:.4}\t{name}");
+		}
+	}
+}
+
+impl StatsReport {
+	pub fn new(
+		output_info: &OutputInfo,
+		settings: &Settings,
+		region: Option<&Region>,
+		pal_vec: &PaletteVec,
+	) -> Self {
+		let OutputInfo {
+			pixels,
+			undo,
+			replaced,
+			survived,
+			diff_pos_place,
+			diff_pos_undo,
+			color_used,
+			milestones,
+			first,
+			last,
+			timeline,
+			place_kind,
+			most_placed,
+			most_undone,
+			members,
+			..
+		} = output_info;
+
+		let colors = ColorReport::ranking(color_used, *pixels, pal_vec);
+
+		let position = |most: &Option<(u32, u32, u32)>| {
+			most.map(|(x, y, times)| PositionReport { x, y, times })
+		};
+
+		// Team only, single user already has the same numbers
+		let members = match settings.team {
+			Some(_) => settings
+				.members()
+				.into_iter()
+				.zip(members.iter())
+				.enumerate()
+				.map(|(index, ((name, _), info))| {
+					let Rgb([r, g, b]) = member_color(index, members.len());
+					MemberReport {
+						name: name.to_owned(),
+						color: format!("#{r:02X}{g:02X}{b:02X}"),
+						pixels: info.pixels,
+						undo: info.undo,
+						survived: info.survived,
+						percent: info.pixels as f32 / *pixels as f32 * 100.0,
+					}
+				})
+				.collect(),
+			None => Vec::new(),
+		};
+
+		Self {
+			canvas: settings.canvas_code.clone(),
+			user: settings.name.clone(),
+			region: region.map(|region| region.name.clone()),
+			start: settings.start.clone(),
+			end: settings.end.clone(),
+			pixels: *pixels,
+			survived: *survived,
+			undo: *undo,
+			replaced: *replaced,
+			diff_pos_place: *diff_pos_place,
+			diff_pos_undo: *diff_pos_undo,
+			most_placed: position(most_placed),
+			most_undone: position(most_undone),
+			place_kind: PlaceKind::ALL
+				.iter()
+				.map(|kind| KindReport {
+					kind: kind.name(),
+					pixels: place_kind[*kind as usize],
+				})
+				.collect(),
+			first: first.as_ref().map(|info| PixelReport::new(info, pal_vec)),
+			last: last.as_ref().map(|info| PixelReport::new(info, pal_vec)),
+			active_days: timeline.days.len(),
+			days: timeline
+				.days
+				.iter()
+				.map(|(day, pixels)| DayReport {
+					day: day.clone(),
+					pixels: *pixels,
+				})
+				.collect(),
+			longest_streak: timeline
+				.longest_streak()
+				.map(|(days, from, to)| StreakReport {
+					days,
+					from: from.to_owned(),
+					to: to.to_owned(),
+				}),
+			busiest_hour: timeline.busiest_hour().map(|(hour, pixels)| HourReport {
+				hour: hour.to_owned(),
+				pixels,
+			}),
+			colors,
+			milestones: milestones
+				.iter()
+				.map(|Milestone { info, kinds }| MilestoneReport {
+					pixel: PixelReport::new(info, pal_vec),
+					kinds: kinds.iter().map(|kind| kind.name()).collect(),
+				})
+				.collect(),
+			members,
+		}
+	}
+
+	/// Tab separated text stats
+	pub fn to_text(&self) -> String {
+		let mut text = String::with_capacity(self.milestones.len() * 50 + 1024);
+
+		let _ = writeln!(&mut text, "Canvas: {}\nUsers: {}", self.canvas, self.user);
+		if let Some(region) = &self.region {
+			let _ = writeln!(&mut text, "Region: {region}");
+		}
+		if self.start.is_some() || self.end.is_some() {
+			let _ = writeln!(
+				&mut text,
+				"Window: {} - {}",
+				self.start.as_deref().unwrap_or("Start"),
+				self.end.as_deref().unwrap_or("End")
+			);
+		}
+		let _ = writeln!(
+			&mut text,
+			"Pixels: {}\nSurvivor: {}\nUndo: {}\nReplace: {}\n\nDifferent Position\nPlace: {}\nUndo: {}",
+			self.pixels,
+			self.survived,
+			self.undo,
+			self.replaced,
+			self.diff_pos_place,
+			self.diff_pos_undo
+		);
+		for (label, most) in [
+			("Most Placed", &self.most_placed),
+			("Most Undone", &self.most_undone),
+		] {
+			if let Some(PositionReport { x, y, times }) = most {
+				let _ = writeln!(&mut text, "{label}: {x}\t{y}\t{times} times");
+			}
+		}
+
+		text.push_str("\nPlace On\n");
+		for KindReport { kind, pixels } in self.place_kind.iter() {
+			let _ = writeln!(&mut text, "{kind}: {pixels}");
+		}
+
+		text.push_str("\nTimeline\n");
+		for (label, pixel) in [("First", &self.first), ("Last", &self.last)] {
+			if let Some(PixelReport {
+				date, x, y, color, ..
+			}) = pixel
+			{
+				let _ = writeln!(&mut text, "{label}: {date}\t{x}\t{y}\t{color}");
+			}
+		}
+		let _ = writeln!(&mut text, "Active Days: {}", self.active_days);
+		if let Some(StreakReport { days, from, to }) = &self.longest_streak {
+			let _ = writeln!(&mut text, "Longest Streak: {days} days ({from} - {to})");
+		}
+		if let Some(HourReport { hour, pixels }) = &self.busiest_hour {
+			let _ = writeln!(&mut text, "Busiest Hour: {hour}:00 ({pixels} pixels)");
+		}
+
+		text.push_str("\nTop Color:\n");
+		ColorReport::write_table(&self.colors, &mut text);
+
+		if !self.members.is_empty() {
+			text.push_str("\nMembers:\nPixels\tSurvivor\tUndo\tPercent\tColor\tMember\n");
+			for MemberReport {
+				name,
+				color,
+				pixels,
+				undo,
+				survived,
+				percent,
+			} in self.members.iter()
+			{
+				let _ = writeln!(
+					&mut text,
+					"{pixels}\t{survived}\t{undo}\t{percent:.4}\t{color}\t{name}"
+				);
+			}
+		}
+
+		text.push_str("\n\nPlace\tDate\tX\tY\tColor\tMilestone\n");
+		for MilestoneReport { pixel, kinds } in self.milestones.iter() {
+			let PixelReport {
+				at,
+				date,
+				x,
+				y,
+				color,
+			} = pixel;
+			let _ = writeln!(
+				&mut text,
+				"{at}\t{date}\t{x}\t{y}\t{color}\t{}",
+				kinds.join(", ")
+			);
+		}
+
+		text
+	}
+}
+
+/// Whole canvas stats, every user, no key needed
+#[derive(Serialize)]
+pub struct CanvasReport {
+	pub canvas: String,
+	pub placed: u32,
+	pub undo: u32,
+	pub colors: Vec<ColorReport>,
+	pub busiest_hour: Option<HourReport>,
+	pub hours: Vec<HourReport>,
+	pub most_overwritten: Vec<PositionReport>,
+	pub final_colors: Vec<ColorReport>,
+}
+
+impl CanvasReport {
+	const MOST_OVERWRITTEN: usize = 10;
+
+	pub fn new(
+		canvas_code: &str,
+		CanvasReplay {
+			placed,
+			undone,
+			color_used,
+			timeline,
+			overwrite_count,
+			..
+		}: &CanvasReplay,
+		final_colors: Vec<(String, i32)>,
+		pal_vec: &PaletteVec,
+	) -> Self {
+		let net_placed = placed.saturating_sub(*undone);
+
+		let mut overwritten = overwrite_count
+			.enumerate_pixels()
+			.filter(|(_, _, px)| px.0[0] != 0)
+			.map(|(x, y, px)| PositionReport {
+				x,
+				y,
+				times: px.0[0],
+			})
+			.collect::<Vec<_>>();
+		overwritten.sort_by(|a, b| b.times.cmp(&a.times).then((a.y, a.x).cmp(&(b.y, b.x))));
+		overwritten.truncate(Self::MOST_OVERWRITTEN);
+
+		let mut final_colors = final_colors;
+		final_colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+		let visible = final_colors.iter().map(|(_, count)| *count as u32).sum();
+
+		Self {
+			canvas: canvas_code.to_owned(),
+			placed: *placed,
+			undo: *undone,
+			colors: ColorReport::ranking(color_used, net_placed, pal_vec),
+			busiest_hour: timeline.busiest_hour().map(|(hour, pixels)| HourReport {
+				hour: hour.to_owned(),
+				pixels,
+			}),
+			hours: timeline
+				.hours
+				.iter()
+				.map(|(hour, pixels)| HourReport {
+					hour: hour.clone(),
+					pixels: *pixels,
+				})
+				.collect(),
+			most_overwritten: overwritten,
+			final_colors: ColorReport::ranked(final_colors.into_iter(), visible),
+		}
+	}
+
+	/// Tab separated text stats
+	pub fn to_text(&self) -> String {
+		let mut text = String::with_capacity(self.hours.len() * 20 + 2048);
+
+		let _ = writeln!(
+			&mut text,
+			"Canvas: {}\nPlaced: {}\nUndo: {}",
+			self.canvas, self.placed, self.undo
+		);
+		if let Some(HourReport { hour, pixels }) = &self.busiest_hour {
+			let _ = writeln!(&mut text, "Busiest Hour: {hour}:00 ({pixels} pixels)");
+		}
+
+		text.push_str("\nColor Popularity:\n");
+		ColorReport::write_table(&self.colors, &mut text);
+
+		text.push_str("\nFinal Canvas Colors:\n");
+		ColorReport::write_table(&self.final_colors, &mut text);
+
+		text.push_str("\nMost Overwritten:\nX\tY\tTimes\n");
+		for PositionReport { x, y, times } in self.most_overwritten.iter() {
+			let _ = writeln!(&mut text, "{x}\t{y}\t{times}");
+		}
+
+		text.push_str("\nPlacements Per Hour:\nHour\tPixels\n");
+		for HourReport { hour, pixels } in self.hours.iter() {
+			let _ = writeln!(&mut text, "{hour}:00\t{pixels}");
+		}
+
+		text
+	}
+}
+
+/// How two users met on the canvas
+#[derive(Serialize)]
+pub struct OverlapReport {
+	pub canvas: String,
+	pub user_a: String,
+	pub user_b: String,
+	pub only_a: usize,
+	pub only_b: usize,
+	pub both: usize,
+	pub a_over_b: u32,
+	pub b_over_a: u32,
+	pub shared_colors: Vec<SharedColorReport>,
+}
+
+#[derive(Serialize)]
+pub struct SharedColorReport {
+	pub name: String,
+	pub a: u32,
+	pub b: u32,
+}
+
+impl OverlapReport {
+	pub fn new(
+		info: &OverlapInfo,
+		settings: &Settings,
+		overlap_setting: &OverlapSetting,
+		pal_vec: &PaletteVec,
+	) -> Self {
+		let [only_a, only_b, both] = info.position_counts();
+		let [a_over_b, b_over_a] = info.overwrote;
+
+		let mut shared = info
+			.colors
+			.iter()
+			.filter(|(_, [a, b])| *a != 0 && *b != 0)
+			.map(|(index, [a, b])| (*index, *a, *b))
+			.collect::<Vec<_>>();
+		shared.sort_by(|x, y| (y.1 + y.2).cmp(&(x.1 + x.2)).then(x.0.cmp(&y.0)));
+
+		Self {
+			canvas: settings.canvas_code.clone(),
+			user_a: settings.name.clone(),
+			user_b: overlap_setting.name.clone(),
+			only_a,
+			only_b,
+			both,
+			a_over_b,
+			b_over_a,
+			shared_colors: shared
+				.into_iter()
+				.map(|(index, a, b)| SharedColorReport {
+					name: pal_vec.info[index as usize].name.to_string(),
+					a,
+					b,
+				})
+				.collect(),
+		}
+	}
+
+	/// Tab separated text stats
+	pub fn to_text(&self) -> String {
+		let (a, b) = (&self.user_a, &self.user_b);
+		let mut text = String::with_capacity(self.shared_colors.len() * 30 + 512);
+
+		let _ = writeln!(
+			&mut text,
+			"Canvas: {}\nUsers: {a}, {b}\n\nPositions\n{a} Only: {}\n{b} Only: {}\nBoth: {}\n\nOverwrote\n{a} Over {b}: {}\n{b} Over {a}: {}",
+			self.canvas, self.only_a, self.only_b, self.both, self.a_over_b, self.b_over_a
+		);
+
+		let _ = writeln!(&mut text, "\nShared Color:\n{a}\t{b}\tColor");
+		for SharedColorReport { name, a, b } in self.shared_colors.iter() {
+			let _ = writeln!(&mut text, "{a}\t{b}\t{name}");
+		}
+
+		text
+	}
+}
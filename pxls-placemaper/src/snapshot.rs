@@ -0,0 +1,364 @@
+use anyhow::{Result, anyhow};
+use image::{Rgba, RgbaImage, imageops::overlay};
+use log::{error, info};
+use std::{
+	collections::HashMap,
+	fmt::{self, Write as _},
+	fs,
+	path::Path,
+};
+
+use crate::{render::count_heatmap, report::CanvasReport, structure::*};
+
+/// Where to stop replaying the logs
+#[derive(Debug, Clone)]
+pub enum SnapshotAt {
+	/// Log line number, inclusive
+	Line(usize),
+	/// Log date, exclusive. Ex: "2024-06-01 12:00"
+	Date(String),
+}
+
+impl SnapshotAt {
+	/// Only digits is a line number, anything else is a date
+	pub fn parse(arg: &str) -> Result<Self> {
+		let arg = arg.trim();
+		if arg.is_empty() {
+			return Err(anyhow!("Missing snapshot timestamp or line number"));
+		}
+		if arg.bytes().all(|byte| byte.is_ascii_digit()) {
+			return Ok(Self::Line(arg.parse()?));
+		}
+		if day_number(arg).is_none() {
+			return Err(anyhow!(
+				"Invalid snapshot date: {arg}, expect \"YYYY-MM-DD HH:MM:SS\""
+			));
+		}
+		Ok(Self::Date(arg.to_owned()))
+	}
+
+	/// None for a line and a date, those are compared during the replay
+	fn is_before(&self, other: &Self) -> Option<bool> {
+		match (self, other) {
+			(Self::Line(this), Self::Line(other)) => Some(this < other),
+			(Self::Date(this), Self::Date(other)) => Some(this < other),
+			_ => None,
+		}
+	}
+
+	fn reached(&self, line: usize, date: &str) -> bool {
+		match self {
+			Self::Line(until) => line > *until,
+			Self::Date(until) => date >= until.as_str(),
+		}
+	}
+
+	/// Safe for file names
+	pub fn label(&self) -> String {
+		match self {
+			Self::Line(line) => format!("Line {line}"),
+			Self::Date(date) => date.replace([':', ','], "-"),
+		}
+	}
+}
+
+impl fmt::Display for SnapshotAt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Line(line) => write!(f, "Line {line}"),
+			Self::Date(date) => f.write_str(date),
+		}
+	}
+}
+
+/// Every user's events replayed on top of the initial canvas
+pub struct CanvasReplay<'a> {
+	lines: std::iter::Peekable<std::iter::Enumerate<std::str::Split<'a, char>>>,
+	pub canvas: RgbaImage,
+	pub placed: u32,
+	pub undone: u32,
+	/// Placed colors after undo
+	pub color_used: ColorUsed,
+	pub timeline: Timeline,
+	/// Placements over a visible pixel per position
+	pub overwrite_count: CountImage,
+	/// xy : last place, for undo
+	prev_place: HashMap<(u32, u32), PrevPlace<'a>>,
+	palette: Vec<Rgba<u8>>,
+}
+
+struct PrevPlace<'a> {
+	/// Color before the place
+	color: Rgba<u8>,
+	index: u8,
+	date: &'a str,
+	overwrote: bool,
+}
+
+impl<'a> CanvasReplay<'a> {
+	pub fn new(
+		logs: &'a str,
+		input_dir: &Path,
+		canvas_code: &str,
+		pal_vec: &PaletteVec,
+	) -> Result<Self> {
+		let img_path = input_dir.join(format!("canvas-{canvas_code}-initial.png"));
+		let canvas = image::open(img_path)?.to_rgba8();
+		let (width, height) = canvas.dimensions();
+		Ok(Self {
+			lines: logs.trim().split('\n').enumerate().peekable(),
+			canvas,
+			placed: 0,
+			undone: 0,
+			color_used: pal_vec.to_color_used(),
+			timeline: Timeline::default(),
+			overwrite_count: CountImage::new(width, height),
+			prev_place: HashMap::new(),
+			palette: pal_vec.info.iter().map(|info| info.rgba).collect(),
+		})
+	}
+
+	/// Apply events until the snapshot point, can be called again with a later point
+	pub fn advance_to(&mut self, until: &SnapshotAt) -> Result<()> {
+		while let Some((at, line)) = self.lines.peek().copied() {
+			let Some(LogLine {
+				date,
+				x,
+				y,
+				color: indexed,
+				undo: is_undo,
+				..
+			}) = LogLine::parse(line)
+			else {
+				error!("Invalid at line {}: {:?}", at, line);
+				self.lines.next();
+				continue;
+			};
+			// Line numbers start at 1
+			if until.reached(at + 1, date) {
+				break;
+			}
+			self.lines.next();
+
+			let Some(old_color) = self.canvas.get_pixel_checked(x, y).copied() else {
+				error!("Out of canvas at line {}: {}, {}", at, x, y);
+				continue;
+			};
+
+			if is_undo {
+				if let Some(PrevPlace {
+					color,
+					index,
+					date,
+					overwrote,
+				}) = self.prev_place.remove(&(x, y))
+				{
+					self.canvas.put_pixel(x, y, color);
+					self.color_used.sub_used(&index);
+					self.timeline.sub_placed(date);
+					if overwrote {
+						self.overwrite_count.get_pixel_mut(x, y).0[0] -= 1;
+					}
+				}
+				self.undone += 1;
+				continue;
+			}
+
+			let Some(color) = self.palette.get(indexed as usize) else {
+				error!("Unknown color at line {}: {}", at, indexed);
+				continue;
+			};
+			let overwrote = old_color.0[3] != 0;
+			if overwrote {
+				self.overwrite_count.get_pixel_mut(x, y).0[0] += 1;
+			}
+			self.prev_place.insert(
+				(x, y),
+				PrevPlace {
+					color: old_color,
+					index: indexed,
+					date,
+					overwrote,
+				},
+			);
+			self.canvas.put_pixel(x, y, *color);
+			self.color_used.add_used(&indexed);
+			self.timeline.add_placed(date);
+			self.placed += 1;
+		}
+		Ok(())
+	}
+
+	/// The snapshot point comes before the next event
+	fn stops_at(&mut self, until: &SnapshotAt) -> bool {
+		self.lines.peek().is_some_and(|(at, line)| {
+			LogLine::parse(line).is_some_and(|log| until.reached(at + 1, log.date))
+		})
+	}
+
+	pub fn advance_to_end(&mut self) -> Result<()> {
+		self.advance_to(&SnapshotAt::Line(usize::MAX))
+	}
+
+	/// Visible pixels per color, non palette color count as initial art
+	pub fn final_colors(&self, pal_vec: &PaletteVec) -> Vec<(String, i32)> {
+		let mut counts: HashMap<Rgba<u8>, i32> = HashMap::new();
+		for px in self.canvas.pixels().filter(|px| px.0[3] != 0) {
+			*counts.entry(*px).or_default() += 1;
+		}
+		let mut initial_art = 0;
+		let mut colors = Vec::with_capacity(pal_vec.info.len() + 1);
+		for (rgba, count) in counts {
+			match pal_vec.info.iter().find(|info| info.rgba == rgba) {
+				Some(info) => colors.push((info.name.to_string(), count)),
+				None => initial_art += count,
+			}
+		}
+		if initial_art != 0 {
+			colors.push(("Initial Art".to_owned(), initial_art));
+		}
+		colors
+	}
+}
+
+/// Full canvas PNG at a timestamp or log line
+pub fn save_snapshot(
+	logs: &str,
+	input_dir: &Path,
+	output: &OutputNaming,
+	Settings { canvas_code, .. }: &Settings,
+	pal_vec: &PaletteVec,
+	until: &SnapshotAt,
+) -> Result<()> {
+	info!("Replaying canvas {} until {}...", canvas_code, until);
+
+	let mut replay = CanvasReplay::new(logs, input_dir, canvas_code, pal_vec)?;
+	replay.advance_to(until)?;
+
+	let path = output.path(None, &format!("Snapshot {}.png", until.label()))?;
+	replay.canvas.save(path)?;
+
+	info!("Saved snapshot.");
+	Ok(())
+}
+
+/// Before, after and changed pixels side by side, with counts
+pub fn save_snapshot_diff(
+	logs: &str,
+	input_dir: &Path,
+	output: &OutputNaming,
+	Settings { canvas_code, .. }: &Settings,
+	pal_vec: &PaletteVec,
+	from: &SnapshotAt,
+	to: &SnapshotAt,
+) -> Result<()> {
+	let order_error = || anyhow!("Diff start {} must be before end {}", from, to);
+	if from.is_before(to) == Some(false) {
+		return Err(order_error());
+	}
+	info!(
+		"Replaying canvas {} from {} to {}...",
+		canvas_code, from, to
+	);
+
+	let mut replay = CanvasReplay::new(logs, input_dir, canvas_code, pal_vec)?;
+	replay.advance_to(from)?;
+	// Line and date resolve to the same point of the logs
+	if from.is_before(to).is_none() && replay.stops_at(to) {
+		return Err(order_error());
+	}
+	let before = replay.canvas.clone();
+	let (placed_before, undone_before) = (replay.placed, replay.undone);
+	replay.advance_to(to)?;
+	let after = &replay.canvas;
+
+	// Unchanged dimmed to gray, changed marked
+	let marker = Rgba([255, 0, 255, 255]);
+	let mut changed = 0;
+	let mut changed_to: HashMap<Rgba<u8>, u32> = HashMap::new();
+	let mut highlight = RgbaImage::new(after.width(), after.height());
+	for (x, y, px) in after.enumerate_pixels() {
+		if before.get_pixel(x, y) != px {
+			changed += 1;
+			*changed_to.entry(*px).or_default() += 1;
+			highlight.put_pixel(x, y, marker);
+			continue;
+		}
+		let [r, g, b, a] = px.0;
+		let gray = ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 2000) as u8;
+		highlight.put_pixel(x, y, Rgba([gray, gray, gray, a]));
+	}
+
+	let gap = 2;
+	let (width, height) = after.dimensions();
+	let mut side_by_side = RgbaImage::new(width * 3 + gap * 2, height);
+	for (n, panel) in [&before, after, &highlight].into_iter().enumerate() {
+		overlay(
+			&mut side_by_side,
+			panel,
+			(n as u32 * (width + gap)) as i64,
+			0,
+		);
+	}
+
+	let label = format!("Diff {} to {}", from.label(), to.label());
+	side_by_side.save(output.path(None, &format!("{label}.png"))?)?;
+
+	let mut changed_to = changed_to
+		.into_iter()
+		.map(|(rgba, count)| {
+			let name = pal_vec
+				.info
+				.iter()
+				.find(|info| info.rgba == rgba)
+				.map_or_else(|| "Initial Art".into(), |info| info.name.clone());
+			(name, count)
+		})
+		.collect::<Vec<_>>();
+	changed_to.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+	let mut text = String::with_capacity(changed_to.len() * 20 + 256);
+	let _ = writeln!(
+		&mut text,
+		"Canvas: {canvas_code}\nFrom: {}\nTo: {}\nChanged Pixels: {changed}\nPlaced: {}\nUndo: {}",
+		from,
+		to,
+		replay.placed - placed_before,
+		replay.undone - undone_before
+	);
+	text.push_str("\nChanged To:\nPixels\tColor\n");
+	for (name, count) in changed_to {
+		let _ = writeln!(&mut text, "{count}\t{name}");
+	}
+	fs::write(output.path(None, &format!("{label}.txt"))?, text)?;
+
+	info!("Saved diff, {} pixels changed.", changed);
+	Ok(())
+}
+
+/// Canvas report in text and JSON, overwrite heatmap
+pub fn save_canvas_report(
+	logs: &str,
+	input_dir: &Path,
+	output: &OutputNaming,
+	Settings { canvas_code, .. }: &Settings,
+	pal_vec: &PaletteVec,
+) -> Result<()> {
+	info!("Replaying canvas {}...", canvas_code);
+
+	let mut replay = CanvasReplay::new(logs, input_dir, canvas_code, pal_vec)?;
+	replay.advance_to_end()?;
+
+	let report = CanvasReport::new(canvas_code, &replay, replay.final_colors(pal_vec), pal_vec);
+
+	fs::write(output.path(None, "Canvas Report.txt")?, report.to_text())?;
+	fs::write(
+		output.path(None, "Canvas Report.json")?,
+		serde_json::to_string_pretty(&report)?,
+	)?;
+	count_heatmap(&replay.overwrite_count)
+		.save(output.path(None, "Canvas Heatmap Overwrite.png")?)?;
+
+	info!("Saved canvas report.");
+	Ok(())
+}
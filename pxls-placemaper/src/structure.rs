@@ -1,23 +1,698 @@
 use anyhow::{Result, anyhow};
-use image::{imageops::crop, *};
+use image::{
+	imageops::{crop, crop_imm},
+	*,
+};
 use log::{error, info};
 use serde::Deserialize;
+use sha256::digest;
 use std::{
 	borrow::Cow,
-	collections::HashMap,
+	collections::{BTreeMap, HashMap},
 	fs,
 	path::{Path, PathBuf},
 };
 
+/// A new optional field also goes in `JobOptions` (job.rs), keep both in sync
 #[derive(Debug, Deserialize)]
 pub struct Settings {
-	pub user_key: String,
+	/// Empty reads the `PXLS_USER_KEY` environment variable
+	#[serde(default)]
+	pub user_key: UserKey,
+	#[serde(default)]
 	pub name: String,
 	pub canvas_code: String,
 	pub palette_code: u8,
+	#[serde(default = "default_pix_th")]
 	pub pix_th: Vec<u32>,
+	#[serde(default = "default_pix_per_frame")]
 	pub pix_per_frame: u32,
+	/// 1 = 10 ms
+	#[serde(default = "default_frame_delay")]
 	pub frame_delay: u16,
+	#[serde(default)]
+	pub regions: Vec<RegionSetting>,
+	#[serde(default)]
+	pub gif: GifSetting,
+	#[serde(default)]
+	pub png_frames: Option<PngFrames>,
+	/// SVG copies of Placemap, Undo and Survivor
+	#[serde(default)]
+	pub svg: bool,
+	/// Report layout files in the input folder
+	#[serde(default)]
+	pub templates: Vec<String>,
+	#[serde(default)]
+	pub milestones: MilestoneSetting,
+	#[serde(default)]
+	pub age_by: AgeBy,
+	/// Log date, inclusive. Ex: "2024-06-01 00:00:00"
+	#[serde(default)]
+	pub start: Option<String>,
+	/// Log date, exclusive
+	#[serde(default)]
+	pub end: Option<String>,
+	/// Several keys as one, replace user_key and name
+	#[serde(default)]
+	pub team: Option<TeamSetting>,
+	/// Compare with another user
+	#[serde(default)]
+	pub overlap: Option<OverlapSetting>,
+	/// File names and folders in the output folder
+	#[serde(default)]
+	pub output: OutputSetting,
+}
+
+impl Settings {
+	/// (Name, key) of the team members, or the single user
+	pub fn members(&self) -> Vec<(&str, &str)> {
+		match &self.team {
+			Some(TeamSetting { members, .. }) => members
+				.iter()
+				.map(|member| (member.name.as_str(), member.user_key.as_str()))
+				.collect(),
+			None => vec![(self.name.as_str(), self.user_key.as_str())],
+		}
+	}
+
+	/// Replace "env:" and "file:" keys by the key they point to
+	pub fn resolve_keys(&mut self, with_keys: bool) -> Result<()> {
+		if !with_keys {
+			return Ok(());
+		}
+		match &mut self.team {
+			Some(TeamSetting { members, .. }) => {
+				for (n, TeamMember { user_key, .. }) in members.iter_mut().enumerate() {
+					user_key.resolve(&format!("team.members[{n}].user_key"))?;
+				}
+			},
+			None => {
+				if self.user_key.0.trim().is_empty() {
+					self.user_key = UserKey(format!("env:{USER_KEY_ENV}"));
+				}
+				self.user_key.resolve("user_key")?;
+			},
+		}
+		if let Some(OverlapSetting { user_key, .. }) = &mut self.overlap {
+			user_key.resolve("overlap.user_key")?;
+		}
+		Ok(())
+	}
+
+	/// Every wrong field at once, before reading the logs. Keys only matter for user stats
+	pub fn validate(&self, input_dir: &Path, with_keys: bool) -> Result<()> {
+		let mut problems = Vec::new();
+		let mut check = |ok: bool, field: &str, problem: String| {
+			if !ok {
+				problems.push(format!("`{field}`: {problem}"));
+			}
+		};
+
+		if let Some(TeamSetting { members, .. }) = &self.team {
+			// Member index is stored in a u8 map, NO_MEMBER marks none
+			check(
+				members.len() < NO_MEMBER as usize,
+				"team.members",
+				format!("at most {} members", NO_MEMBER as usize - 1),
+			);
+		}
+		match &self.team {
+			_ if !with_keys => {},
+			Some(TeamSetting { members, .. }) => {
+				check(
+					!members.is_empty(),
+					"team.members",
+					"needs at least one member".into(),
+				);
+				for (n, TeamMember { user_key, .. }) in members.iter().enumerate() {
+					check(
+						is_user_key(user_key),
+						&format!("team.members[{n}].user_key"),
+						key_problem(user_key),
+					);
+				}
+			},
+			None => {
+				check(
+					is_user_key(&self.user_key),
+					"user_key",
+					key_problem(&self.user_key),
+				);
+				check(
+					!self.name.trim().is_empty(),
+					"name",
+					"must not be empty".into(),
+				);
+			},
+		}
+		if let (true, Some(OverlapSetting { user_key, .. })) = (with_keys, &self.overlap) {
+			check(
+				is_user_key(user_key),
+				"overlap.user_key",
+				key_problem(user_key),
+			);
+		}
+		check(
+			!self.canvas_code.trim().is_empty(),
+			"canvas_code",
+			"must not be empty".into(),
+		);
+
+		let palette_path = input_dir.join(format!("palette_{}_paintnet.txt", self.palette_code));
+		check(
+			palette_path.is_file(),
+			"palette_code",
+			format!("{} not found", palette_path.display()),
+		);
+
+		let canvas_path = input_dir.join(format!("canvas-{}-initial.png", self.canvas_code));
+		match image::image_dimensions(&canvas_path) {
+			Ok(canvas_size) => {
+				for (n, RegionSetting { area, .. }) in self.regions.iter().enumerate() {
+					if let Some(problem) = area.problem(input_dir, canvas_size) {
+						check(false, &format!("regions[{n}].area"), problem);
+					}
+				}
+			},
+			Err(err) => check(
+				false,
+				"canvas_code",
+				format!("{}: {}", canvas_path.display(), err),
+			),
+		}
+
+		check(
+			self.pix_per_frame > 0,
+			"pix_per_frame",
+			"must be greater than 0".into(),
+		);
+		check(
+			self.gif.duration != Some(0),
+			"gif.duration",
+			"must be greater than 0".into(),
+		);
+		// Each part starts with a keyframe
+		check(
+			self.gif.max_frames.is_none_or(|max_frames| max_frames >= 2),
+			"gif.max_frames",
+			"must be at least 2, keyframe and one frame".into(),
+		);
+		check(
+			self.gif.max_size != Some(0),
+			"gif.max_size",
+			"must be greater than 0".into(),
+		);
+		if let Some(OverlaySetting { scale, .. }) = &self.gif.overlay {
+			check(
+				*scale > 0,
+				"gif.overlay.scale",
+				"must be greater than 0".into(),
+			);
+		}
+
+		for (field, date) in [("start", &self.start), ("end", &self.end)] {
+			if let Some(date) = date {
+				check(
+					day_number(date).is_some(),
+					field,
+					format!("{date:?} is not \"YYYY-MM-DD HH:MM:SS\""),
+				);
+			}
+		}
+		if let (Some(start), Some(end)) = (&self.start, &self.end) {
+			check(start < end, "end", format!("must be after start {start:?}"));
+		}
+
+		if problems.is_empty() {
+			return Ok(());
+		}
+		Err(anyhow!("Invalid settings.ron\n  {}", problems.join("\n  ")))
+	}
+
+	/// Log dates share the same layout, compare as text
+	pub fn in_window(&self, date: &str) -> bool {
+		self.start.as_deref().is_none_or(|start| date >= start)
+			&& self.end.as_deref().is_none_or(|end| date < end)
+	}
+}
+
+pub fn default_pix_th() -> Vec<u32> {
+	vec![1, 5, 10, 50, 100, 1000, 5000, 10000]
+}
+
+pub fn default_pix_per_frame() -> u32 {
+	25
+}
+
+pub fn default_frame_delay() -> u16 {
+	4
+}
+
+/// Pxls log key, 512 hex characters
+pub const USER_KEY_LEN: usize = 512;
+/// Key of the single user when `user_key` is empty
+pub const USER_KEY_ENV: &str = "PXLS_USER_KEY";
+
+/// Pxls log key: the key, "env:NAME" or "file:path/to/key.txt".
+/// Only the last characters are shown in logs and reports
+#[derive(Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct UserKey(String);
+
+impl UserKey {
+	/// Key for hashing, never for display
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	fn resolve(&mut self, field: &str) -> Result<()> {
+		let key = self.0.trim();
+		let resolved = if let Some(var) = key.strip_prefix("env:") {
+			std::env::var(var)
+				.map_err(|err| anyhow!("`{field}`: environment variable {var}: {err}"))?
+		} else if let Some(path) = key.strip_prefix("file:") {
+			fs::read_to_string(path).map_err(|err| anyhow!("`{field}`: key file {path}: {err}"))?
+		} else {
+			return Ok(());
+		};
+		let source = key.to_owned();
+		self.0 = resolved.trim().to_owned();
+		info!("Read {} from {}: {}", field, source, self);
+		Ok(())
+	}
+}
+
+impl std::fmt::Display for UserKey {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self.0.get(self.0.len().saturating_sub(4)..) {
+			Some(tail) if self.0.len() >= 16 => write!(f, "****{tail}"),
+			_ => f.write_str("****"),
+		}
+	}
+}
+
+impl std::fmt::Debug for UserKey {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "UserKey({self})")
+	}
+}
+
+fn is_user_key(UserKey(user_key): &UserKey) -> bool {
+	user_key.len() == USER_KEY_LEN && user_key.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn key_problem(UserKey(user_key): &UserKey) -> String {
+	match user_key.bytes().find(|byte| !byte.is_ascii_hexdigit()) {
+		Some(byte) => format!("only hex characters, found {:?}", byte as char),
+		None => format!(
+			"expect {} hex characters, found {}",
+			USER_KEY_LEN,
+			user_key.len()
+		),
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TeamSetting {
+	pub name: String,
+	pub members: Vec<TeamMember>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct TeamMember {
+	pub name: String,
+	pub user_key: UserKey,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OverlapSetting {
+	pub name: String,
+	pub user_key: UserKey,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutputSetting {
+	/// Variables: {canvas}, {kind}, {name}, {artifact}, {date}
+	#[serde(default = "default_pattern")]
+	pub pattern: String,
+	#[serde(default)]
+	pub layout: OutputLayout,
+}
+
+impl Default for OutputSetting {
+	fn default() -> Self {
+		Self {
+			pattern: default_pattern(),
+			layout: OutputLayout::default(),
+		}
+	}
+}
+
+fn default_pattern() -> String {
+	"C{canvas} {kind} {name} {artifact}".to_owned()
+}
+
+/// Sub folders of the output folder
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum OutputLayout {
+	/// Everything in the output folder
+	#[default]
+	Flat,
+	/// "C{canvas}"
+	Canvas,
+	/// "{name}"
+	User,
+	/// "C{canvas}/{name}"
+	CanvasUser,
+}
+
+/// Output path of every file from the pattern and layout
+pub struct OutputNaming {
+	dir: PathBuf,
+	pattern: String,
+	layout: OutputLayout,
+	canvas: String,
+	/// Run date "YYYY-MM-DD"
+	date: String,
+}
+
+impl OutputNaming {
+	pub fn new(
+		dir: &Path,
+		Settings {
+			canvas_code,
+			output: OutputSetting { pattern, layout },
+			..
+		}: &Settings,
+	) -> Result<Self> {
+		for variable in pattern_variables(pattern) {
+			if !["canvas", "kind", "name", "artifact", "date"].contains(&variable) {
+				return Err(anyhow!(
+					"Unknown variable {{{}}} in output pattern {:?}",
+					variable,
+					pattern
+				));
+			}
+		}
+		// Several users and canvases in one run share a folder unless the layout splits them
+		let (user_dir, canvas_dir) = match layout {
+			OutputLayout::Flat => (false, false),
+			OutputLayout::Canvas => (false, true),
+			OutputLayout::User => (true, false),
+			OutputLayout::CanvasUser => (true, true),
+		};
+		for (variable, needed) in [
+			("artifact", true),
+			("name", !user_dir),
+			("canvas", !canvas_dir),
+		] {
+			if needed && !pattern.contains(&format!("{{{variable}}}")) {
+				return Err(anyhow!(
+					"Output pattern {:?} needs {{{}}} with layout {:?}, files would overwrite each other",
+					pattern,
+					variable,
+					layout
+				));
+			}
+		}
+		Ok(Self {
+			dir: dir.to_path_buf(),
+			pattern: pattern.clone(),
+			layout: *layout,
+			canvas: canvas_code.clone(),
+			date: civil_date(today()),
+		})
+	}
+
+	/// Path of an artifact like "Placemap.png", no name for whole canvas files
+	pub fn path(&self, name: Option<&str>, artifact: &str) -> Result<PathBuf> {
+		self.kind_path("", name, artifact)
+	}
+
+	/// Path of a report file, `kind` like "Stats" goes before the artifact without {kind}
+	pub fn kind_path(&self, kind: &str, name: Option<&str>, artifact: &str) -> Result<PathBuf> {
+		let (artifact, extension) = artifact.rsplit_once('.').unwrap_or((artifact, ""));
+		let artifact = match self.pattern.contains("{kind}") {
+			true => artifact.to_owned(),
+			false => format!("{kind} {artifact}"),
+		};
+		let file_name = self
+			.pattern
+			.replace("{canvas}", &self.canvas)
+			.replace("{kind}", kind)
+			.replace("{name}", name.unwrap_or_default())
+			.replace("{artifact}", &artifact)
+			.replace("{date}", &self.date);
+		// Empty variables leave extra spaces
+		let mut file_name = file_name
+			.split(' ')
+			.filter(|word| !word.is_empty())
+			.collect::<Vec<_>>()
+			.join(" ");
+		if !extension.is_empty() {
+			file_name = format!("{file_name}.{extension}");
+		}
+
+		let canvas_dir = format!("C{}", self.canvas);
+		let dir = match (self.layout, name) {
+			(OutputLayout::Flat, _) => self.dir.clone(),
+			(OutputLayout::Canvas, _) | (OutputLayout::CanvasUser, None) => {
+				self.dir.join(canvas_dir)
+			},
+			(OutputLayout::User, Some(name)) => self.dir.join(name),
+			(OutputLayout::User, None) => self.dir.clone(),
+			(OutputLayout::CanvasUser, Some(name)) => self.dir.join(canvas_dir).join(name),
+		};
+		fs::create_dir_all(&dir)?;
+		Ok(dir.join(file_name))
+	}
+}
+
+fn pattern_variables(pattern: &str) -> impl Iterator<Item = &str> {
+	pattern
+		.split('{')
+		.skip(1)
+		.filter_map(|part| part.split_once('}').map(|(variable, _)| variable))
+}
+
+/// Animated placemap pacing
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct GifSetting {
+	#[serde(default)]
+	pub repeat: GifRepeat,
+	/// Last frame delay, 1 = 10 ms
+	#[serde(default)]
+	pub hold: Option<u16>,
+	/// Slow start and end, fast middle
+	#[serde(default)]
+	pub ease: bool,
+	/// Total length, 1 = 10 ms, replace `pix_per_frame`
+	#[serde(default)]
+	pub duration: Option<u32>,
+	/// Split into numbered parts after this many frames
+	#[serde(default)]
+	pub max_frames: Option<u32>,
+	/// Split into numbered parts after this many MB
+	#[serde(default)]
+	pub max_size: Option<u32>,
+	#[serde(default)]
+	pub overlay: Option<OverlaySetting>,
+}
+
+/// Text drawn on every GIF frame
+#[derive(Debug, Clone, Deserialize)]
+pub struct OverlaySetting {
+	#[serde(default)]
+	pub corner: Corner,
+	/// Font pixel size
+	#[serde(default = "default_scale")]
+	pub scale: u32,
+	#[serde(default)]
+	pub name: bool,
+	#[serde(default)]
+	pub counter: bool,
+	#[serde(default)]
+	pub date: bool,
+}
+
+fn default_scale() -> u32 {
+	1
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum Corner {
+	#[default]
+	TopLeft,
+	TopRight,
+	BottomLeft,
+	BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum GifRepeat {
+	Finite(u16),
+	Infinite,
+}
+
+impl Default for GifRepeat {
+	fn default() -> Self {
+		GifRepeat::Finite(1)
+	}
+}
+
+/// Animation frames as numbered PNG
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum PngFrames {
+	/// Whole canvas state each frame
+	Full,
+	/// Only changed area, offset in manifest
+	Cropped,
+}
+
+/// Milestone rules in addition to `pix_th`
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct MilestoneSetting {
+	/// Every N pixels
+	#[serde(default)]
+	pub every: Option<u32>,
+	#[serde(default)]
+	pub first_of_day: bool,
+	#[serde(default)]
+	pub first_color: bool,
+}
+
+/// Value of placement age gradient map
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum AgeBy {
+	/// Ordinal among user placements
+	#[default]
+	Order,
+	/// Seconds since first placement
+	Time,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegionSetting {
+	pub name: String,
+	pub area: RegionArea,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum RegionArea {
+	/// x, y, width, height
+	Rect(u32, u32, u32, u32),
+	/// PNG file in "input" folder, any non-transparent pixel is inside
+	Mask(String),
+}
+
+impl RegionArea {
+	/// Empty or outside the canvas
+	pub fn problem(
+		&self,
+		input_dir: &Path,
+		(canvas_width, canvas_height): (u32, u32),
+	) -> Option<String> {
+		match self {
+			RegionArea::Rect(left, top, width, height) => {
+				let fits = |start: &u32, size: &u32, canvas: u32| {
+					start.checked_add(*size).is_some_and(|end| end <= canvas)
+				};
+				if *width == 0 || *height == 0 {
+					Some("width and height must be greater than 0".into())
+				} else if !fits(left, width, canvas_width) || !fits(top, height, canvas_height) {
+					Some(format!(
+						"Rect({left}, {top}, {width}, {height}) is outside the {canvas_width}x{canvas_height} canvas"
+					))
+				} else {
+					None
+				}
+			},
+			RegionArea::Mask(file_name) => match image::image_dimensions(input_dir.join(file_name))
+			{
+				Err(err) => Some(format!("mask {file_name}: {err}")),
+				Ok((width, height)) if width > canvas_width || height > canvas_height => {
+					Some(format!(
+						"mask {file_name} is {width}x{height}, larger than the {canvas_width}x{canvas_height} canvas"
+					))
+				},
+				Ok(_) => None,
+			},
+		}
+	}
+}
+
+pub struct Region {
+	pub name: String,
+	pub left: u32,
+	pub top: u32,
+	pub width: u32,
+	pub height: u32,
+	pub mask: Option<GrayImage>,
+}
+
+impl Region {
+	pub fn new(
+		input_dir: &Path,
+		RegionSetting { name, area }: &RegionSetting,
+		canvas_size: (u32, u32),
+	) -> Result<Region> {
+		if let Some(problem) = area.problem(input_dir, canvas_size) {
+			return Err(anyhow!("Region {}: {}", name, problem));
+		}
+		let region = match area {
+			RegionArea::Rect(left, top, width, height) => Region {
+				name: name.clone(),
+				left: *left,
+				top: *top,
+				width: *width,
+				height: *height,
+				mask: None,
+			},
+			RegionArea::Mask(file_name) => {
+				let mask_img = image::open(input_dir.join(file_name))?.to_luma_alpha8();
+				let mask = GrayImage::from_fn(mask_img.width(), mask_img.height(), |x, y| {
+					Luma([(mask_img.get_pixel(x, y).0[1] != 0) as u8])
+				});
+				if mask.pixels().all(|px| px.0[0] == 0) {
+					return Err(anyhow!("Region {} mask {} is empty", name, file_name));
+				}
+				let mut bound = mask.clone();
+				let (left, top) = bound.crop_in(0);
+				Region {
+					name: name.clone(),
+					left: left as u32,
+					top: top as u32,
+					width: bound.width(),
+					height: bound.height(),
+					mask: Some(mask),
+				}
+			},
+		};
+		info!("Region {} ready.", region.name);
+		Ok(region)
+	}
+
+	pub fn contains(&self, x: u32, y: u32) -> bool {
+		let within = |at: u32, start: u32, size: u32| {
+			at >= start && start.checked_add(size).is_none_or(|end| at < end)
+		};
+		let in_rect = within(x, self.left, self.width) && within(y, self.top, self.height);
+		in_rect
+			&& self
+				.mask
+				.as_ref()
+				.is_none_or(|mask| mask.get_pixel_checked(x, y).is_some_and(|px| px.0[0] != 0))
+	}
+
+	/// Region area of a full canvas image
+	pub fn crop_from<P: Pixel + 'static>(
+		&self,
+		img: &ImageBuffer<P, Vec<P::Subpixel>>,
+	) -> ImageBuffer<P, Vec<P::Subpixel>> {
+		let width = self.width.min(img.width().saturating_sub(self.left));
+		let height = self.height.min(img.height().saturating_sub(self.top));
+		crop_imm(img, self.left, self.top, width, height).to_image()
+	}
 }
 
 pub struct PaletteInfo {
@@ -114,9 +789,23 @@ pub struct ImageCollection {
 	pub place: GrayImage,
 	pub undo: GrayImage,
 	pub survivor: GrayImage,
-	pub gif: Vec<GrayImageCropped>,
+	/// Placed pixel by [`PlaceKind`]
+	pub kind: [GrayImage; 4],
+	/// [`PlaceKind`] of the next pixel placed by user at each position
+	pub owner: GrayImage,
+	pub undo_count: CountImage,
+	pub place_count: CountImage,
+	/// [`AgeBy`] value + 1 of placed pixel, 0 for empty
+	pub place_age: CountImage,
+	/// Team member index of survived pixel, [`NO_MEMBER`] for none
+	pub member: GrayImage,
 }
 
+pub const NO_MEMBER: u8 = u8::MAX;
+
+/// Times of action at each position
+pub type CountImage = ImageBuffer<Luma<u32>, Vec<u32>>;
+
 pub struct GrayImageCropped {
 	pub left: u16,
 	pub top: u16,
@@ -162,22 +851,224 @@ impl ImageCollection {
 	}
 
 	pub fn new(intial: GrayImage) -> Self {
+		let (width, height) = intial.dimensions();
 		Self {
 			place: intial.clone(),
 			undo: intial.clone(),
 			survivor: intial.clone(),
-			gif: vec![GrayImageCropped::new_pure(intial)],
+			kind: std::array::from_fn(|_| intial.clone()),
+			owner: GrayImage::from_pixel(width, height, PlaceKind::Fresh.owner()),
+			undo_count: CountImage::new(width, height),
+			place_count: CountImage::new(width, height),
+			place_age: CountImage::new(width, height),
+			member: GrayImage::from_pixel(width, height, Luma([NO_MEMBER])),
 		}
 	}
+
+	/// Mark non-transparent pixels of initial canvas as art
+	pub fn with_initial_art(mut self, initial_canvas: &DynamicImage) -> Self {
+		let initial_canvas = initial_canvas.to_luma_alpha8();
+		for (x, y, px) in initial_canvas.enumerate_pixels() {
+			if px.0[1] != 0 {
+				self.owner.put_pixel(x, y, PlaceKind::OverInitial.owner());
+			}
+		}
+		self
+	}
+
+	/// Palette indexed placemaps with file naming
+	pub fn placemaps(&self) -> Vec<(&GrayImage, String)> {
+		let mut placemaps = vec![
+			(&self.place, "Placemap.png".to_string()),
+			(&self.undo, "Placemap Undo.png".to_string()),
+			(&self.survivor, "Placemap Survivor.png".to_string()),
+		];
+		for kind in PlaceKind::ALL {
+			placemaps.push((
+				&self.kind[kind as usize],
+				format!("Placemap {}.png", kind.name()),
+			));
+		}
+		placemaps
+	}
+
+	/// Placemaps also written as SVG, with file naming
+	pub fn vector_maps(&self) -> [(&GrayImage, &'static str); 3] {
+		[
+			(&self.place, "Placemap.svg"),
+			(&self.undo, "Placemap Undo.svg"),
+			(&self.survivor, "Placemap Survivor.svg"),
+		]
+	}
+
+	/// Count maps with file naming
+	pub fn heatmaps(&self) -> [(&CountImage, &'static str); 2] {
+		[
+			(&self.undo_count, "Heatmap Undo.png"),
+			(&self.place_count, "Heatmap Place.png"),
+		]
+	}
+}
+
+/// What was at the position before user placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceKind {
+	/// No one touched since initial canvas
+	Fresh,
+	OverInitial,
+	OverSelf,
+	OverOther,
 }
 
+impl PlaceKind {
+	pub const ALL: [PlaceKind; 4] = [
+		PlaceKind::Fresh,
+		PlaceKind::OverInitial,
+		PlaceKind::OverSelf,
+		PlaceKind::OverOther,
+	];
+
+	pub fn from_owner(owner: Luma<u8>) -> Self {
+		Self::ALL[owner.0[0] as usize]
+	}
+
+	pub fn owner(self) -> Luma<u8> {
+		Luma([self as u8])
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			PlaceKind::Fresh => "Fresh",
+			PlaceKind::OverInitial => "Over Initial",
+			PlaceKind::OverSelf => "Over Self",
+			PlaceKind::OverOther => "Over Other",
+		}
+	}
+}
+
+#[derive(Clone)]
 pub struct PixelInfoAt {
 	pub at: u32,
+	pub date: String,
 	pub pixel: u8,
 	pub x: u32,
 	pub y: u32,
 }
 
+/// One tab separated line of the pixel logs
+pub struct LogLine<'a> {
+	pub date: &'a str,
+	pub x: u32,
+	pub y: u32,
+	pub color: u8,
+	pub undo: bool,
+	rand_hash: &'a str,
+	/// x, y and color as written, the hash is over the text
+	fields: [&'a str; 3],
+}
+
+impl<'a> LogLine<'a> {
+	/// None for a line with missing fields or bad numbers
+	pub fn parse(line: &'a str) -> Option<Self> {
+		let splited: Vec<&str> = line.split('\t').collect();
+		let [date, rand_hash, x, y, color_index, action] = splited[..] else {
+			return None;
+		};
+		Some(Self {
+			date,
+			x: x.parse().ok()?,
+			y: y.parse().ok()?,
+			color: color_index.parse().ok()?,
+			undo: action == "user undo",
+			rand_hash,
+			fields: [x, y, color_index],
+		})
+	}
+
+	pub fn is_key_owner(&self, user_key: &str) -> bool {
+		let [x, y, color_index] = self.fields;
+		digest([self.date, x, y, color_index, user_key].join(",")) == self.rand_hash
+	}
+
+	/// Index of the member who placed it
+	pub fn member(&self, members: &[(&str, &str)]) -> Option<usize> {
+		members
+			.iter()
+			.position(|(_, user_key)| self.is_key_owner(user_key))
+	}
+}
+
+/// Placements that become the first one when those before are undone.
+/// The member is cleared once they placed again, their placement can't be undone anymore
+#[derive(Default)]
+pub struct FirstPlaced(Vec<(Option<usize>, PixelInfoAt)>);
+
+impl FirstPlaced {
+	/// `info` is None for a placement outside the window
+	pub fn place(&mut self, member: usize, info: Option<&PixelInfoAt>) {
+		if let Some(n) = self.0.iter().position(|(owner, _)| *owner == Some(member)) {
+			self.0[n].0 = None;
+			// Nothing after a kept placement can become first
+			self.0.truncate(n + 1);
+		}
+		if let Some(info) = info
+			&& self.0.last().is_none_or(|(owner, _)| owner.is_some())
+		{
+			self.0.push((Some(member), info.clone()));
+		}
+	}
+
+	pub fn undo(&mut self, member: usize) {
+		if let Some(n) = self.0.iter().position(|(owner, _)| *owner == Some(member)) {
+			self.0.remove(n);
+		}
+	}
+
+	pub fn first(&self) -> Option<PixelInfoAt> {
+		self.0.first().map(|(_, info)| info.clone())
+	}
+}
+
+/// What a placement covered, put back on undo
+pub struct PlaceUndo {
+	pub color: u8,
+	pub placed: Luma<u8>,
+	pub survivor: Luma<u8>,
+	pub owner: Luma<u8>,
+	pub member: Luma<u8>,
+	pub kind: PlaceKind,
+	pub kind_pix: Luma<u8>,
+	pub age: Luma<u32>,
+	pub date: String,
+	pub in_window: bool,
+	pub out_window: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MilestoneKind {
+	/// Listed in `pix_th`
+	Threshold,
+	Every,
+	FirstOfDay,
+	FirstColor,
+}
+
+impl MilestoneKind {
+	pub fn name(self) -> &'static str {
+		match self {
+			MilestoneKind::Threshold => "Pixel th",
+			MilestoneKind::Every => "Every",
+			MilestoneKind::FirstOfDay => "First of Day",
+			MilestoneKind::FirstColor => "First Color",
+		}
+	}
+}
+
+pub struct Milestone {
+	pub info: PixelInfoAt,
+	pub kinds: Vec<MilestoneKind>,
+}
+
 #[derive(Default)]
 pub struct OutputInfo {
 	pub pixels: u32,
@@ -187,18 +1078,202 @@ pub struct OutputInfo {
 	pub diff_pos_place: usize,
 	pub diff_pos_undo: usize,
 	pub color_used: ColorUsed,
-	pub pix_place: Vec<PixelInfoAt>,
+	pub milestones: Vec<Milestone>,
+	pub first: Option<PixelInfoAt>,
+	pub last: Option<PixelInfoAt>,
+	pub timeline: Timeline,
+	pub transition: ColorTransition,
+	/// Pixels by [`PlaceKind`]
+	pub place_kind: [u32; 4],
+	/// x, y, times
+	pub most_placed: Option<(u32, u32, u32)>,
+	/// x, y, times
+	pub most_undone: Option<(u32, u32, u32)>,
+	/// Same order as [`Settings::members`]
+	pub members: Vec<MemberInfo>,
+}
+
+#[derive(Default, Clone)]
+pub struct MemberInfo {
+	pub pixels: u32,
+	pub undo: u32,
+	pub survived: usize,
 }
 
 impl OutputInfo {
-	pub fn new(color_used: ColorUsed) -> Self {
+	pub fn new(color_used: ColorUsed, members: usize) -> Self {
 		Self {
 			color_used,
+			members: vec![MemberInfo::default(); members],
 			..Default::default()
 		}
 	}
 }
 
+/// (Replaced color, Placed color) : times
+#[derive(Default)]
+pub struct ColorTransition(pub HashMap<(u8, u8), i32>);
+
+impl ColorTransition {
+	pub fn add_used(&mut self, from: u8, to: u8) {
+		*self.0.entry((from, to)).or_default() += 1;
+	}
+
+	pub fn sub_used(&mut self, from: u8, to: u8) {
+		*self.0.entry((from, to)).or_default() -= 1;
+	}
+
+	pub fn get(&self, from: u8, to: u8) -> i32 {
+		self.0.get(&(from, to)).copied().unwrap_or_default()
+	}
+
+	pub fn max(&self) -> i32 {
+		self.0.values().copied().max().unwrap_or_default()
+	}
+
+	/// Rows are replaced color, columns are placed color
+	pub fn to_csv(&self, pal_vec: &PaletteVec) -> String {
+		let mut csv = String::from("Replaced\\Placed");
+		for pal_info in pal_vec.info.iter() {
+			csv.push(',');
+			csv.push_str(&pal_info.name);
+		}
+		csv.push('\n');
+		for (from, pal_info) in pal_vec.info.iter().enumerate() {
+			csv.push_str(&pal_info.name);
+			for to in 0..pal_vec.info.len() {
+				csv.push(',');
+				csv.push_str(&self.get(from as u8, to as u8).to_string());
+			}
+			csv.push('\n');
+		}
+		csv
+	}
+}
+
+/// Placements per day and per hour
+#[derive(Default)]
+pub struct Timeline {
+	/// "YYYY-MM-DD" : pixels
+	pub days: BTreeMap<String, u32>,
+	/// "YYYY-MM-DD HH" : pixels
+	pub hours: BTreeMap<String, u32>,
+}
+
+impl Timeline {
+	pub fn add_placed(&mut self, date: &str) {
+		let (Some(day), Some(hour)) = (date.get(..10), date.get(..13)) else {
+			return;
+		};
+		*self.days.entry(day.to_owned()).or_default() += 1;
+		*self.hours.entry(hour.to_owned()).or_default() += 1;
+	}
+
+	pub fn placed_on_day(&self, date: &str) -> u32 {
+		date.get(..10)
+			.and_then(|day| self.days.get(day))
+			.copied()
+			.unwrap_or_default()
+	}
+
+	pub fn sub_placed(&mut self, date: &str) {
+		let (Some(day), Some(hour)) = (date.get(..10), date.get(..13)) else {
+			return;
+		};
+		for (key, map) in [(day, &mut self.days), (hour, &mut self.hours)] {
+			if let Some(count) = map.get_mut(key) {
+				*count -= 1;
+				if *count == 0 {
+					map.remove(key);
+				}
+			}
+		}
+	}
+
+	/// Longest run of consecutive active days: (days, first day, last day)
+	pub fn longest_streak(&self) -> Option<(u32, &str, &str)> {
+		let mut best: Option<(u32, &str, &str)> = None;
+		let mut current: Option<(u32, &str, i64)> = None;
+		for day in self.days.keys() {
+			let Some(day_num) = day_number(day) else {
+				continue;
+			};
+			current = match current {
+				Some((run, from, prev_num)) if prev_num + 1 == day_num => {
+					Some((run + 1, from, day_num))
+				},
+				_ => Some((1, day, day_num)),
+			};
+			let (run, from, _) = current.unwrap();
+			if best.is_none_or(|(best_run, _, _)| run > best_run) {
+				best = Some((run, from, day));
+			}
+		}
+		best
+	}
+
+	pub fn busiest_hour(&self) -> Option<(&str, u32)> {
+		self.hours.iter().fold(
+			None,
+			|best: Option<(&str, u32)>, (hour, count)| match best {
+				Some((_, best_count)) if best_count >= *count => best,
+				_ => Some((hour, *count)),
+			},
+		)
+	}
+}
+
+/// Seconds since 1970-01-01 of "YYYY-MM-DD HH:MM:SS"
+pub fn date_seconds(date: &str) -> Option<i64> {
+	let mut split = date.get(11..19)?.split(':');
+	let hour: i64 = split.next()?.parse().ok()?;
+	let minute: i64 = split.next()?.parse().ok()?;
+	let second: i64 = split.next()?.parse().ok()?;
+	Some(day_number(date)? * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Days since 1970-01-01 of "YYYY-MM-DD"
+pub fn day_number(day: &str) -> Option<i64> {
+	let mut split = day.get(..10)?.split('-');
+	let year: i64 = split.next()?.parse().ok()?;
+	let month: i64 = split.next()?.parse().ok()?;
+	let day: i64 = split.next()?.parse().ok()?;
+
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let month_shift = (month + 9) % 12;
+	let day_of_year = (153 * month_shift + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	Some(era * 146097 + day_of_era - 719468)
+}
+
+/// "YYYY-MM-DD" of days since 1970-01-01, inverse of `day_number`
+pub fn civil_date(days: i64) -> String {
+	let days = days + 719468;
+	let era = days.div_euclid(146097);
+	let day_of_era = days - era * 146097;
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_shift = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * month_shift + 2) / 5 + 1;
+	let month = if month_shift < 10 {
+		month_shift + 3
+	} else {
+		month_shift - 9
+	};
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Days since 1970-01-01 of the system clock, UTC
+pub fn today() -> i64 {
+	std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map_or(0, |elapsed| elapsed.as_secs() as i64 / 86400)
+}
+
 #[derive(Default)]
 pub struct ColorUsed(pub HashMap<u8, i32>);
 
@@ -211,8 +1286,12 @@ impl ColorUsed {
 		*self.0.get_mut(index).unwrap() -= 1;
 	}
 
-	pub fn to_vec(self) -> Vec<(u8, i32)> {
-		self.0.into_iter().collect::<Vec<_>>()
+	pub fn used(&self, index: &u8) -> i32 {
+		self.0.get(index).copied().unwrap_or_default()
+	}
+
+	pub fn to_vec(&self) -> Vec<(u8, i32)> {
+		self.0.iter().map(|(a, b)| (*a, *b)).collect::<Vec<_>>()
 	}
 }
 
@@ -220,6 +1299,8 @@ pub trait ExtentGrayImage {
 	/// Self, x, y
 	fn crop_in(&mut self, transparent: u8) -> (u16, u16);
 	fn save_in_color(&self, palette: &[(u8, u8, u8)], path: PathBuf) -> Result<()>;
+	/// Draw non-transparent pixels of frame on top
+	fn paint(&mut self, frame: &GrayImageCropped, transparent: u8);
 	// fn diff_out(&mut self, other: &GrayImageCropped, transparent: u8);
 }
 
@@ -303,6 +1384,14 @@ impl ExtentGrayImage for GrayImage {
 		Ok(())
 	}
 
+	fn paint(&mut self, GrayImageCropped { left, top, img }: &GrayImageCropped, transparent: u8) {
+		for (x, y, px) in img.enumerate_pixels() {
+			if px.0[0] != transparent {
+				self.put_pixel(x + *left as u32, y + *top as u32, *px);
+			}
+		}
+	}
+
 	// fn diff_out(&mut self, other: &GrayImageCropped, transparent: u8) {
 	// 	let transparent = Luma([transparent]);
 	// 	other
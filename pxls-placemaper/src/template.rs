@@ -0,0 +1,244 @@
+use anyhow::{Result, anyhow};
+use log::info;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Report layout from a file in the input folder
+///
+/// `{pixels}`, `{first.date}`: value of the stats field, empty if missing.
+/// `{#colors}...{/colors}`: repeated for each item, fields of the item first.
+/// Same for a single value: shown once if present and not false.
+/// `{{` and `}}`: literal braces.
+pub struct ReportTemplate {
+	pub file_name: String,
+	text: String,
+}
+
+impl ReportTemplate {
+	pub fn load(input_dir: &Path, file_name: &str) -> Result<Self> {
+		let text = fs::read_to_string(input_dir.join(file_name))
+			.map_err(|err| anyhow!("Template {}: {}", file_name, err))?;
+		// Fail on the layout before processing the logs
+		render(&text, &[&Value::Null]).map_err(|err| anyhow!("Template {}: {}", file_name, err))?;
+		info!("Complete reading Template {}.", file_name);
+		// Template in a sub folder still lands next to the stats
+		let file_name = Path::new(file_name).file_name().map_or_else(
+			|| file_name.to_owned(),
+			|name| name.to_string_lossy().into_owned(),
+		);
+		Ok(Self { file_name, text })
+	}
+
+	pub fn render(&self, data: &Value) -> Result<String> {
+		render(&self.text, &[data]).map_err(|err| anyhow!("Template {}: {}", self.file_name, err))
+	}
+}
+
+/// Innermost context last
+fn render(text: &str, contexts: &[&Value]) -> Result<String> {
+	let mut output = String::with_capacity(text.len() * 2);
+	let mut rest = text;
+
+	while let Some(open) = rest.find(['{', '}']) {
+		output.push_str(&rest[..open]);
+		let tail = &rest[open..];
+
+		if tail.starts_with("{{") || tail.starts_with("}}") {
+			output.push_str(&tail[..1]);
+			rest = &tail[2..];
+			continue;
+		}
+		if tail.starts_with('}') {
+			return Err(anyhow!("Unmatched '}}', use '}}}}' for a brace"));
+		}
+
+		let close = tail
+			.find('}')
+			.ok_or_else(|| anyhow!("Unclosed '{{' near {:?}", preview(tail)))?;
+		let tag = tail[1..close].trim();
+		let after = &tail[close + 1..];
+
+		if let Some(name) = tag.strip_prefix('#') {
+			let (inner, remain) = split_section(after, name)?;
+			match lookup(contexts, name) {
+				Some(Value::Array(items)) => {
+					for item in items {
+						output.push_str(&render(inner, &with_context(contexts, item))?);
+					}
+				},
+				None | Some(Value::Null) | Some(Value::Bool(false)) => {
+					// Still check the layout inside
+					render(inner, contexts)?;
+				},
+				Some(value) => {
+					output.push_str(&render(inner, &with_context(contexts, value))?);
+				},
+			}
+			rest = remain;
+			continue;
+		}
+		if tag.starts_with('/') {
+			return Err(anyhow!("Unmatched {{{}}}", tag));
+		}
+		if tag.is_empty() {
+			return Err(anyhow!("Empty placeholder '{{}}'"));
+		}
+
+		if let Some(value) = lookup(contexts, tag) {
+			output.push_str(&display(value));
+		}
+		rest = after;
+	}
+	output.push_str(rest);
+	Ok(output)
+}
+
+/// Inner text and text after `{/name}`, same name can nest
+fn split_section<'a>(text: &'a str, name: &str) -> Result<(&'a str, &'a str)> {
+	let (open_tag, close_tag) = (format!("{{#{name}}}"), format!("{{/{name}}}"));
+	let mut depth = 0;
+	let mut search = 0;
+	loop {
+		let next_close = text[search..]
+			.find(&close_tag)
+			.ok_or_else(|| anyhow!("Missing {} for section", close_tag))?
+			+ search;
+		match text[search..next_close].find(&open_tag) {
+			Some(nested) => {
+				depth += 1;
+				search += nested + open_tag.len();
+			},
+			None if depth == 0 => {
+				return Ok((&text[..next_close], &text[next_close + close_tag.len()..]));
+			},
+			None => {
+				depth -= 1;
+				search = next_close + close_tag.len();
+			},
+		}
+	}
+}
+
+fn with_context<'a>(contexts: &[&'a Value], value: &'a Value) -> Vec<&'a Value> {
+	let mut contexts = contexts.to_vec();
+	contexts.push(value);
+	contexts
+}
+
+/// Dotted path, from innermost context out
+fn lookup<'a>(contexts: &[&'a Value], path: &str) -> Option<&'a Value> {
+	let mut keys = path.split('.');
+	let first = keys.next()?;
+	let found = contexts
+		.iter()
+		.rev()
+		.find_map(|context| context.get(first))?;
+	keys.try_fold(found, |value, key| value.get(key))
+}
+
+fn display(value: &Value) -> String {
+	match value {
+		Value::Null => String::new(),
+		Value::String(text) => text.clone(),
+		Value::Number(number) if number.is_f64() => {
+			format!("{:.4}", number.as_f64().unwrap_or_default())
+		},
+		Value::Array(items) => items.iter().map(display).collect::<Vec<_>>().join(", "),
+		value => value.to_string(),
+	}
+}
+
+fn preview(text: &str) -> String {
+	text.chars().take(20).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn render_json(text: &str, data: Value) -> Result<String> {
+		render(text, &[&data])
+	}
+
+	#[test]
+	fn placeholders() {
+		let data = json!({"pixels": 42, "name": "Tester", "percent": 0.5, "none": null});
+		assert_eq!(
+			render_json("{name}: {pixels} ({percent}){none}{missing}", data).unwrap(),
+			"Tester: 42 (0.5000)"
+		);
+	}
+
+	#[test]
+	fn dotted_path() {
+		let data = json!({"first": {"date": "2024-06-01", "pixel": {"x": 3}}});
+		assert_eq!(
+			render_json("{first.date} at {first.pixel.x}{first.nope.x}", data).unwrap(),
+			"2024-06-01 at 3"
+		);
+	}
+
+	#[test]
+	fn array_section() {
+		let data = json!({"unit": "px", "colors": [{"name": "Red", "used": 2}, {"name": "Blue", "used": 1}]});
+		assert_eq!(
+			render_json("{#colors}{name} {used}{unit};{/colors}", data).unwrap(),
+			"Red 2px;Blue 1px;"
+		);
+	}
+
+	#[test]
+	fn object_section() {
+		let data = json!({"name": "Outer", "longest_streak": {"days": 3}});
+		assert_eq!(
+			render_json(
+				"{#longest_streak}{days} days by {name}{/longest_streak}",
+				data
+			)
+			.unwrap(),
+			"3 days by Outer"
+		);
+	}
+
+	#[test]
+	fn hidden_section() {
+		let data = json!({"off": false, "empty": null, "items": []});
+		assert_eq!(
+			render_json(
+				"a{#off}x{/off}{#empty}y{/empty}{#missing}z{/missing}{#items}w{/items}b",
+				data
+			)
+			.unwrap(),
+			"ab"
+		);
+	}
+
+	#[test]
+	fn nested_same_name() {
+		let data = json!({"a": {"v": 1, "a": {"v": 2}}});
+		assert_eq!(
+			render_json("{#a}{v}[{#a}{v}{/a}]{v}{/a}", data).unwrap(),
+			"1[2]1"
+		);
+	}
+
+	#[test]
+	fn literal_braces() {
+		let data = json!({"pixels": 7});
+		assert_eq!(
+			render_json("{{pixels}} = {{{pixels}}}", data).unwrap(),
+			"{pixels} = {7}"
+		);
+	}
+
+	#[test]
+	fn errors() {
+		let missing = render_json("{#colors}{name}", json!({})).unwrap_err();
+		assert!(missing.to_string().contains("Missing {/colors}"));
+		let unmatched = render_json("a } b", json!({})).unwrap_err();
+		assert!(unmatched.to_string().contains("Unmatched '}'"));
+		// Layout inside a hidden section is still checked
+		assert!(render_json("{#off}}{/off}", json!({"off": false})).is_err());
+	}
+}